@@ -0,0 +1,17 @@
+// build.rs
+// 启用 grpc 功能时从 proto/ai_search.proto 生成服务代码；protoc 用 protoc-bin-vendored 自带的，不需要另外安装
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    compile_protos().expect("编译 proto/ai_search.proto 失败");
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = prost_build::Config::new();
+    config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure()
+        .build_client(true) // 客户端代码留给其他 Rust 服务直接引用
+        .compile_protos_with_config(config, &["proto/ai_search.proto"], &["proto"])?;
+    Ok(())
+}
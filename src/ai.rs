@@ -1,8 +1,57 @@
 // src/ai.rs
-use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
-use anyhow::Result;
+use fastembed::{TextEmbedding, InitOptions, InitOptionsUserDefined, EmbeddingModel, TokenizerFiles, UserDefinedEmbeddingModel};
+use anyhow::{Context, Result, anyhow};
 use jieba_rs::Jieba;
 use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::config::AiConfig;
+
+// 默认使用的嵌入模型
+pub const MODEL: EmbeddingModel = EmbeddingModel::BGESmallZHV15;
+
+// 分词器需要的文件，和 fastembed 从 Hugging Face 下载的一致
+const TOKENIZER_FILES: [&str; 4] = ["tokenizer.json", "config.json", "special_tokens_map.json", "tokenizer_config.json"];
+
+impl AiConfig {
+    pub fn model(&self) -> Result<EmbeddingModel> {
+        match &self.model_id {
+            Some(id) => id.parse::<EmbeddingModel>().map_err(|e| anyhow!("{} (可用模型见 fastembed 文档)", e)),
+            None => Ok(MODEL),
+        }
+    }
+
+    // 没有指定本地模型目录时，fastembed 下载和缓存模型的目录
+    pub fn cache_dir(&self) -> PathBuf {
+        PathBuf::from(fastembed::get_cache_dir())
+    }
+
+    // model_path 指向的本地模型目录里必须有的文件：模型仓库的 onnx 文件和分词器配置。
+    // 目录布局和 Hugging Face 上的仓库一致，可以用 huggingface-cli download --local-dir 准备
+    pub fn local_model_files(&self) -> Result<Vec<String>> {
+        let model = self.model()?;
+        let info = TextEmbedding::get_model_info(&model)?;
+        let mut files = vec![info.model_file.clone()];
+        files.extend(TOKENIZER_FILES.iter().map(|file| file.to_string()));
+        Ok(files)
+    }
+
+    // 交互模式启动时显示的 AI 状态
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return "已禁用: 不生成标签，不做意图识别".to_string();
+        }
+        let model = self.model()
+            .and_then(|m| TextEmbedding::get_model_info(&m).map(|info| info.model_code.clone()))
+            .unwrap_or_else(|e| format!("无效 ({})", e));
+        match &self.model_path {
+            Some(dir) => format!("已启用: 模型 {}，本地模型目录 {:?}", model, dir),
+            None => format!("已启用: 模型 {}，缓存目录 {:?}", model, self.cache_dir()),
+        }
+    }
+}
 
 pub struct BertModel {
     model: TextEmbedding,
@@ -11,18 +60,34 @@ pub struct BertModel {
 
 impl BertModel {
     pub fn new() -> Result<Self> {
-        // 修复 1 & 2: 使用 new() 方法初始化，并修正模型名称
-        let model = TextEmbedding::try_new(
-            InitOptions::new(EmbeddingModel::BGESmallZHV15)
-                .with_show_download_progress(true)
-        )?;
+        Self::load(&AiConfig::default())
+    }
+
+    // 配置了 model_path 时只从本地目录加载，缺文件直接报错，不会联网下载；
+    // 否则按 model_id 从缓存目录加载，缓存里没有时自动下载
+    pub fn load(options: &AiConfig) -> Result<Self> {
+        let model = match &options.model_path {
+            Some(dir) => load_local_model(dir, options)?,
+            // 修复 1 & 2: 使用 new() 方法初始化，并修正模型名称
+            None => TextEmbedding::try_new(
+                InitOptions::new(options.model()?)
+                    .with_cache_dir(options.cache_dir())
+                    .with_show_download_progress(true)
+            )?,
+        };
 
         Ok(Self {
             model,
             jieba: Jieba::new(),
         })
     }
-    pub fn refine_query(&self, origin_query: &str) -> String {
+}
+
+// 生成关键词的模型。索引和查询优化只依赖这个 trait，测试里可以换成不加载模型的实现
+pub trait Tagger: Send + Sync {
+    fn extract_keywords(&self, text: &str, top_k: usize) -> Result<Vec<String>>;
+
+    fn refine_query(&self, origin_query: &str) -> String {
         // 1. 如果输入太短（比如就两个字），直接返回，不用 AI 猜
         if origin_query.chars().count() < 4 {
             return origin_query.to_string();
@@ -51,8 +116,10 @@ impl BertModel {
             }
         }
     }
+}
 
-    pub fn extract_keywords(&self, text: &str, top_k: usize) -> Result<Vec<String>> {
+impl Tagger for BertModel {
+    fn extract_keywords(&self, text: &str, top_k: usize) -> Result<Vec<String>> {
         let truncated_text = if text.chars().count() > 512 {
             text.chars().take(512).collect::<String>()
         } else {
@@ -98,6 +165,67 @@ impl BertModel {
     }
 }
 
+// 按需加载的模型：一次性的服务 (MCP / JSON-RPC) 只有真正要打标签时才加载，启动不用等模型。
+// 加载失败只记日志，之后都按没有模型处理 (文件照样索引，只是没有标签)
+pub struct LazyBert {
+    config: AiConfig,
+    model: OnceLock<Option<Arc<dyn Tagger>>>,
+}
+
+impl LazyBert {
+    pub fn new(config: AiConfig) -> Self {
+        Self { config, model: OnceLock::new() }
+    }
+
+    // 配置里是否启用了 AI (不触发加载)
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn get(&self) -> Option<Arc<dyn Tagger>> {
+        self.model
+            .get_or_init(|| {
+                if !self.config.enabled {
+                    return None;
+                }
+                eprintln!(" [AI] 正在加载 BERT 模型 (首次运行需下载)...");
+                match BertModel::load(&self.config) {
+                    Ok(bert) => Some(Arc::new(bert)),
+                    Err(e) => {
+                        eprintln!(" [AI] 模型加载失败，索引时不生成标签: {:#}", e);
+                        None
+                    }
+                }
+            })
+            .clone()
+    }
+}
+
+fn load_local_model(dir: &Path, options: &AiConfig) -> Result<TextEmbedding> {
+    let files = options.local_model_files()?;
+    let missing: Vec<&str> = files.iter().map(String::as_str).filter(|file| !dir.join(file).is_file()).collect();
+    if !missing.is_empty() {
+        return Err(anyhow!("本地模型目录 {:?} 缺少文件: {} (指定了 model_path 时不会自动下载)", dir, missing.join(", ")));
+    }
+
+    let read = |file: &str| fs::read(dir.join(file)).with_context(|| format!("无法读取模型文件 {:?}", dir.join(file)));
+    let tokenizer_files = TokenizerFiles {
+        tokenizer_file: read("tokenizer.json")?,
+        config_file: read("config.json")?,
+        special_tokens_map_file: read("special_tokens_map.json")?,
+        tokenizer_config_file: read("tokenizer_config.json")?,
+    };
+
+    // 池化方式和量化方式跟着 model_id 走，和下载的同一个模型保持一致
+    let model = options.model()?;
+    let mut user_model = UserDefinedEmbeddingModel::new(read(&files[0])?, tokenizer_files)
+        .with_quantization(TextEmbedding::get_quantization_mode(&model));
+    if let Some(pooling) = TextEmbedding::get_default_pooling_method(&model) {
+        user_model = user_model.with_pooling(pooling);
+    }
+    TextEmbedding::try_new_from_user_defined(user_model, InitOptionsUserDefined::new())
+}
+
 // 辅助函数放在 impl 块外面
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
@@ -0,0 +1,203 @@
+// api.rs
+// 对外接口 (HTTP / gRPC / JSON-RPC / MCP) 共用的请求和响应类型，字段名就是 JSON 里的键名
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tantivy::directory::error::LockError;
+use tantivy::schema::Schema;
+use tantivy::{Index, TantivyError};
+
+use crate::ai::Tagger;
+use crate::events::EventBus;
+use crate::config::Config;
+use crate::indexer::{self, PathRejection};
+use crate::search::{QueryParseError, SearchOptions, SearchPage};
+
+// 搜索请求：{"query": "...", "offset": 0, "limit": 10, "file_type": "pdf"}，分页字段都可以省略
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+pub struct SearchRequest {
+    #[cfg_attr(feature = "mcp", schemars(description = "搜索词，支持 AND / OR 等查询语法"))]
+    pub query: String,
+    #[serde(flatten)]
+    pub options: SearchOptions,
+}
+
+pub type SearchResponse = SearchPage;
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+pub struct IndexRequest {
+    #[cfg_attr(feature = "mcp", schemars(description = "文件路径"))]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchIndexRequest {
+    pub paths: Vec<PathBuf>,
+}
+
+// 单个文件的索引结果，title 和 error 只会有一个
+#[derive(Debug, Serialize)]
+pub struct IndexResult {
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchIndexResponse {
+    pub indexed: usize,
+    pub failed: usize,
+    pub results: Vec<IndexResult>,
+}
+
+impl BatchIndexResponse {
+    // 批量索引：路径不合法的文件直接记为失败，其余文件按 commit_every_docs 分批提交 (阻塞调用)
+    pub fn run(request: BatchIndexRequest, index: &Index, schema: &Schema, bert: Option<&dyn Tagger>, config: &Config, events: &EventBus) -> Result<Self> {
+        let mut results = Vec::with_capacity(request.paths.len());
+        let mut accepted = Vec::new();
+        for path in request.paths {
+            match indexer::resolve_external_path(&path, config) {
+                Ok(resolved) => accepted.push(resolved),
+                Err(rejection) => {
+                    let error = format!("{:?}: {}", path, rejection);
+                    results.push(IndexResult { path, title: None, error: Some(error) });
+                }
+            }
+        }
+
+        let outcomes = indexer::index_paths(&accepted, index, schema, bert, config, events)?;
+        for (path, outcome) in accepted.into_iter().zip(outcomes) {
+            results.push(match outcome {
+                Ok(title) => IndexResult { path, title: Some(title), error: None },
+                Err(e) => IndexResult { path, title: None, error: Some(format!("{:#}", e)) },
+            });
+        }
+
+        let failed = results.iter().filter(|r| r.error.is_some()).count();
+        Ok(Self { indexed: results.len() - failed, failed, results })
+    }
+}
+
+// 删除请求，path 用搜索结果里返回的写法
+#[derive(Debug, Deserialize)]
+pub struct DeleteRequest {
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteResponse {
+    pub deleted: bool, // 索引里本来就没有这个路径时为 false
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    pub num_docs: u64,
+    pub num_segments: usize,
+    pub ai_enabled: bool,
+    pub watch_roots: Vec<PathBuf>,
+}
+
+impl StatsResponse {
+    pub fn collect(index: &Index, ai_enabled: bool, config: &Config) -> Result<Self> {
+        let searcher = index.reader()?.searcher();
+        Ok(Self {
+            num_docs: searcher.num_docs(),
+            num_segments: searcher.segment_readers().len(),
+            ai_enabled,
+            watch_roots: config.watch.root_paths(),
+        })
+    }
+}
+
+// 输入补全：按前缀列出已有的标签
+#[derive(Debug, Deserialize)]
+pub struct SuggestRequest {
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_suggest_limit")]
+    pub limit: usize,
+}
+
+pub const DEFAULT_SUGGEST_LIMIT: usize = 10;
+
+fn default_suggest_limit() -> usize {
+    DEFAULT_SUGGEST_LIMIT
+}
+
+#[derive(Debug, Serialize)]
+pub struct Suggestion {
+    pub tag: String,
+    pub count: usize, // 带这个标签的文档数
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+// 接口错误的类别；HTTP 和 gRPC 各自映射成自己的状态码，同一种错误在两边含义一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorKind {
+    InvalidRequest, // 参数不对
+    Unauthorized,   // 缺少或错误的访问令牌
+    Forbidden,      // 路径不在监控目录下
+    NotFound,       // 索引里没有这个文件
+    Unprocessable,  // 文件本身处理失败 (提取文本出错等)
+    Busy,           // 写入锁被监控线程等占着，稍后重试即可
+    Internal,
+}
+
+impl ApiErrorKind {
+    // 内部错误的分类：查询语法错误算调用方的问题，写入锁被占用算 Busy，其余都算服务端错误
+    pub fn classify(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<QueryParseError>().is_some() {
+            return ApiErrorKind::InvalidRequest;
+        }
+        match e.downcast_ref::<TantivyError>() {
+            Some(TantivyError::LockFailure(LockError::LockBusy, _)) => ApiErrorKind::Busy,
+            _ => ApiErrorKind::Internal,
+        }
+    }
+}
+
+impl From<PathRejection> for ApiErrorKind {
+    fn from(rejection: PathRejection) -> Self {
+        match rejection {
+            PathRejection::OutsideRoots => ApiErrorKind::Forbidden,
+            PathRejection::NotFound | PathRejection::Filtered => ApiErrorKind::InvalidRequest,
+        }
+    }
+}
+
+// 检查访问令牌 (HTTP 和 gRPC 共用)：按常数时间比较，响应时间不会随猜对的前缀变长而变化
+#[cfg(any(feature = "server", feature = "grpc"))]
+pub fn token_matches(expected: &str, provided: Option<&str>) -> bool {
+    use subtle::ConstantTimeEq;
+    provided.is_some_and(|provided| bool::from(provided.as_bytes().ct_eq(expected.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parse_errors_are_invalid_requests() {
+        let e = anyhow::Error::new(QueryParseError("Syntax Error".to_string()));
+        assert_eq!(ApiErrorKind::classify(&e), ApiErrorKind::InvalidRequest);
+        assert_eq!(ApiErrorKind::classify(&anyhow::anyhow!("磁盘坏了")), ApiErrorKind::Internal);
+    }
+
+    #[cfg(any(feature = "server", feature = "grpc"))]
+    #[test]
+    fn token_must_match_exactly() {
+        assert!(token_matches("secret", Some("secret")));
+        for provided in [None, Some(""), Some("secre"), Some("secret2"), Some("Secret")] {
+            assert!(!token_matches("secret", provided), "{:?}", provided);
+        }
+    }
+}
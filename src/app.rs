@@ -0,0 +1,225 @@
+// app.rs
+// 交互模式和服务模式的运行状态：索引、模型、后台扫描和监控线程，以及统一的退出流程
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use anyhow::Result;
+
+use tantivy::Index;
+use tantivy::schema::Schema;
+
+use crate::ai::Tagger;
+use crate::config::{Config, ConfigHandle};
+use crate::events::{EngineEvent, EventBus};
+use crate::indexer::{self, WatcherHandle};
+
+pub struct AppState {
+    pub index: Index,
+    pub schema: Schema,
+    pub bert: Option<Arc<dyn Tagger>>, // --no-ai 时为 None
+    config: ConfigHandle,             // 和监控线程共享，reload 时整体替换
+    events: Arc<EventBus>,            // 扫描、监控和接口层的索引操作都往这里发布事件
+    shutdown_flag: Arc<AtomicBool>,
+    indexing: Mutex<Option<JoinHandle<()>>>, // 后台扫描线程，扫描完成后由它启动监控
+    watcher: Mutex<Option<WatcherHandle>>,
+}
+
+impl AppState {
+    pub fn new(index: Index, schema: Schema, bert: Option<Arc<dyn Tagger>>, config: ConfigHandle) -> Self {
+        Self {
+            index,
+            schema,
+            bert,
+            config,
+            events: Arc::default(),
+            shutdown_flag: Arc::new(AtomicBool::new(false)),
+            indexing: Mutex::new(None),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    // 当前配置的快照；过滤规则等可能被 reload 替换，每次用的时候重新取
+    pub fn config(&self) -> Arc<Config> {
+        self.config.current()
+    }
+
+    pub fn config_handle(&self) -> &ConfigHandle {
+        &self.config
+    }
+
+    // 订阅索引事件 (HTTP 的 /events 用它)
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.events
+    }
+
+    // Ctrl-C 处理函数和扫描过程共用这个标志
+    pub fn shutdown_flag(&self) -> Arc<AtomicBool> {
+        self.shutdown_flag.clone()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown_flag.load(Ordering::SeqCst)
+    }
+
+    pub fn start_watcher(&self, roots: Vec<PathBuf>) {
+        let handle = indexer::start_watcher_thread(roots, self.index.clone(), self.schema.clone(), self.bert.clone(), self.config.clone(), self.events.clone());
+        *self.watcher.lock().unwrap() = Some(handle);
+    }
+
+    // 在后台线程里扫描所有监控目录的现有文件，扫描完再启动监控。
+    // 服务模式要先绑定好端口再调用这里：扫描期间 /healthz 等接口照常可用，
+    // /events 的客户端也能收到从 ai_status、scan_started 开始的全部事件
+    pub fn start_indexing(self: &Arc<Self>) {
+        let app = self.clone();
+        let handle = thread::spawn(move || {
+            let config = app.config();
+            app.events.publish(EngineEvent::AiStatus { enabled: app.bert.is_some(), detail: config.ai.describe() });
+
+            let roots = config.watch.root_paths();
+            let scanned = indexer::scan_existing_files(&roots, &app.index, &app.schema, app.bert.as_deref(), &app.config, &app.events, &app.shutdown_flag);
+            if let Err(e) = scanned {
+                eprintln!(" [后台] 扫描现有文件失败: {:#}", e);
+            }
+            if !app.is_shutting_down() {
+                app.start_watcher(roots);
+            }
+        });
+        *self.indexing.lock().unwrap() = Some(handle);
+    }
+
+    // 运行时替换配置：扩展名、排除路径、文件大小上限、标签数等立即对扫描和监控生效；
+    // 存储路径、模型、监控目录有变化时返回错误并保持原配置
+    pub fn reload_config(&self, new: Arc<Config>) -> Result<()> {
+        self.config.reload(new)
+    }
+
+    // 统一的退出流程 (quit 和 Ctrl-C 都走这里)，重复调用是安全的：
+    // 1. 置位退出标志，正在进行的扫描会在当前文件处理完后停下，等扫描线程做完最后一次提交
+    // 2. 通知监控线程停止并等待它退出；线程里正在处理的文件会完成 commit 后才返回
+    // 3. 所有 IndexWriter 都已提交并释放，写入锁随之释放
+    pub fn shutdown(&self) -> Result<()> {
+        self.shutdown_flag.store(true, Ordering::SeqCst);
+
+        // 扫描线程可能正要启动监控，先等它结束，再取监控线程的句柄
+        if let Some(handle) = self.indexing.lock().unwrap().take()
+            && handle.join().is_err()
+        {
+            eprintln!("扫描线程异常退出");
+        }
+
+        if let Some(handle) = self.watcher.lock().unwrap().take() {
+            println!(" [后台] 正在停止监控线程...");
+            handle.stop_and_join();
+        }
+
+        println!(" [退出] 索引已保存，再见！");
+        Ok(())
+    }
+}
+
+// 安装 Ctrl-C 处理：第一次只置位退出标志，由主循环走正常的 shutdown 流程；
+// 如果退出过程卡住，再按一次 Ctrl-C 直接强制退出
+pub fn install_ctrlc_handler(shutdown_flag: Arc<AtomicBool>) -> Result<()> {
+    ctrlc::set_handler(move || {
+        if shutdown_flag.swap(true, Ordering::SeqCst) {
+            eprintln!("\n [退出] 强制退出");
+            std::process::exit(130);
+        }
+        eprintln!("\n [退出] 收到 Ctrl-C，正在保存索引并退出 (再按一次强制退出)...");
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+    use tantivy::IndexWriter;
+    use crate::config::{MIN_WRITER_HEAP_SIZE, RootConfig};
+
+    // 生成第 n 个标签时置位退出标志，相当于扫描到一半按了 Ctrl-C
+    struct StopAfter {
+        remaining: AtomicUsize,
+        flag: Arc<AtomicBool>,
+    }
+
+    impl Tagger for StopAfter {
+        fn extract_keywords(&self, _text: &str, _top_k: usize) -> Result<Vec<String>> {
+            if self.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+                self.flag.store(true, Ordering::SeqCst);
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn shutdown_commits_the_interrupted_scan_and_releases_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        for i in 0..20 {
+            fs::write(root.join(format!("{}.txt", i)), "退出时正在扫描的文件").unwrap();
+        }
+
+        // 单线程扫描、扫描完之前不提交：退出时已处理的文件全都还没提交
+        let mut config = Config::default();
+        config.index.storage_path = dir.path().join("storage");
+        config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+        config.index.commit_every_docs = 100_000;
+        config.scan.parallelism = 1;
+        config.watch.roots = vec![RootConfig::new(&root)];
+        let (index, schema) = indexer::init_persistent_index(&config.index.storage_path).unwrap();
+        let mut app = AppState::new(index, schema, None, ConfigHandle::new(config.clone()));
+        app.bert = Some(Arc::new(StopAfter { remaining: AtomicUsize::new(5), flag: app.shutdown_flag() }));
+        let app = Arc::new(app);
+
+        app.start_indexing();
+        // 第 5 个文件处理完后扫描线程看到退出标志停下，shutdown 等它做完最后一次提交
+        while !app.is_shutting_down() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        app.shutdown().unwrap();
+        app.shutdown().unwrap(); // 重复调用是安全的
+        assert!(app.watcher.lock().unwrap().is_none(), "中断的扫描不应该再启动监控");
+
+        let reader = app.index.reader().unwrap();
+        reader.reload().unwrap();
+        let num_docs = reader.searcher().num_docs();
+        assert_eq!(num_docs, 5);
+
+        // 写入锁已经释放：同一个索引和重新打开的索引都能拿到 writer
+        let writer: IndexWriter = app.index.writer(MIN_WRITER_HEAP_SIZE).unwrap();
+        drop(writer);
+        drop(app);
+        let (index, _schema) = indexer::init_persistent_index(&config.index.storage_path).unwrap();
+        assert_eq!(index.reader().unwrap().searcher().num_docs(), num_docs);
+        let _writer: IndexWriter = index.writer(MIN_WRITER_HEAP_SIZE).unwrap();
+    }
+
+    #[test]
+    fn shutdown_stops_the_watcher_started_after_the_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.ai.enabled = false;
+        config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+        config.watch.roots = vec![RootConfig::new(dir.path())];
+        let (index, schema) = indexer::init_ram_index();
+        let app = Arc::new(AppState::new(index, schema, None, ConfigHandle::new(config)));
+
+        app.start_indexing();
+        for _ in 0..100 {
+            if app.watcher.lock().unwrap().is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(app.watcher.lock().unwrap().is_some());
+
+        app.shutdown().unwrap();
+        assert!(app.watcher.lock().unwrap().is_none());
+        let _writer: IndexWriter = app.index.writer(MIN_WRITER_HEAP_SIZE).unwrap();
+    }
+}
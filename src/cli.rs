@@ -0,0 +1,401 @@
+// cli.rs
+// 命令行子命令的参数解析与输出格式化 (交互模式之外的一次性命令)
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use tantivy::Index;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::{self, Config};
+use crate::models::StoredDoc;
+use crate::search::{get_document, list_tags};
+
+pub const DEFAULT_TAGS_LIMIT: usize = 30;
+pub const DEFAULT_SERVE_ADDR: &str = "127.0.0.1:7700";
+pub const DEFAULT_GRPC_ADDR: &str = "127.0.0.1:50051";
+
+// 解析后的命令行：全局选项 + 子命令
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub config_path: Option<PathBuf>, // --config <path>，不指定时读取默认路径 (不存在则用默认配置)
+    pub overrides: Overrides,
+    pub command: Command,
+}
+
+// 命令行上覆盖配置文件的选项
+#[derive(Debug, Default, PartialEq)]
+pub struct Overrides {
+    pub no_ai: bool,
+    pub model_path: Option<PathBuf>,
+    pub model_id: Option<String>,
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut Config) {
+        if self.no_ai {
+            config.ai.enabled = false;
+        }
+        if let Some(path) = &self.model_path {
+            config.ai.model_path = Some(path.clone());
+        }
+        if let Some(id) = &self.model_id {
+            config.ai.model_id = Some(id.clone());
+        }
+    }
+}
+
+impl Cli {
+    pub fn config_file(&self) -> &Path {
+        self.config_path.as_deref().unwrap_or(Path::new(config::CONFIG_PATH))
+    }
+
+    // 加载配置文件并应用命令行覆盖；显式指定的配置文件必须存在
+    pub fn load_config(&self) -> Result<Config> {
+        let mut config = match &self.config_path {
+            Some(path) => Config::load_from(path)?,
+            None => Config::load_or_default(self.config_file())?,
+        };
+        self.overrides.apply(&mut config);
+        Ok(config)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Interactive,          // 不带子命令：后台监控 + 前台搜索
+    Tags(TagsArgs),       // ai_search tags ...
+    Doctor { deep: bool }, // ai_search doctor [--deep]
+    Tui,                  // ai_search tui：终端界面
+    ConfigShow { defaults: bool }, // ai_search config show [--effective | --defaults]
+    Serve(ServeArgs),     // ai_search serve [--addr ..] [--token ..]：HTTP 接口
+    Grpc(ServeArgs),      // ai_search grpc [--addr ..] [--token ..]：gRPC 接口
+    Mcp,                  // ai_search mcp：stdio 上的 MCP 服务
+    Rpc,                  // ai_search rpc：stdio 上的 JSON-RPC，给编辑器插件用
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ServeArgs {
+    pub addr: SocketAddr,
+    pub token: Option<String>, // 设置后，修改索引的接口需要 Authorization: Bearer <token>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TagsArgs {
+    pub limit: usize,
+    pub prefix: String,
+    pub of: Option<String>, // --of <path>：只看某个文件的标签
+    pub json: bool,
+}
+
+impl Default for TagsArgs {
+    fn default() -> Self {
+        Self { limit: DEFAULT_TAGS_LIMIT, prefix: String::new(), of: None, json: false }
+    }
+}
+
+// 子命令前面可以写的全局选项
+const GLOBAL_FLAGS: &[&str] = &["--config", "--no-ai", "--model-path", "--model-id"];
+
+// 解析命令行参数 (不含程序名本身)
+// 全局选项 (--config / --no-ai / --model-path / --model-id) 只能写在子命令前面，
+// 子命令后面的参数都交给子命令自己解析，比如 tags --prefix --no-ai 查的是以 "--no-ai" 开头的标签
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Cli> {
+    let mut config_path = None;
+    let mut overrides = Overrides::default();
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next_if(|arg| GLOBAL_FLAGS.contains(&arg.as_str())) {
+        match arg.as_str() {
+            "--config" => config_path = Some(next_value(&mut args, "--config")?.into()),
+            "--no-ai" => overrides.no_ai = true,
+            "--model-path" => overrides.model_path = Some(next_value(&mut args, "--model-path")?.into()),
+            _ => overrides.model_id = Some(next_value(&mut args, "--model-id")?),
+        }
+    }
+
+    let rest: Vec<String> = args.collect();
+    let misplaced = rest.iter().skip(1).any(|arg| GLOBAL_FLAGS.contains(&arg.as_str()));
+    let command = parse_command(rest.into_iter()).map_err(|e| {
+        if misplaced { anyhow!("{} (全局选项 {} 要写在子命令前面)", e, GLOBAL_FLAGS.join(" / ")) } else { e }
+    })?;
+    Ok(Cli { config_path, overrides, command })
+}
+
+fn parse_command<I: Iterator<Item = String>>(mut args: I) -> Result<Command> {
+    match args.next().as_deref() {
+        None => Ok(Command::Interactive),
+        Some("tags") => parse_tags_args(args).map(Command::Tags),
+        Some("doctor") => parse_doctor_args(args),
+        Some("tui") => match args.next() {
+            None => Ok(Command::Tui),
+            Some(other) => Err(anyhow!("tui 不支持的参数: {}", other)),
+        },
+        Some("config") => parse_config_args(args),
+        Some("serve") => parse_serve_args(args, "serve", DEFAULT_SERVE_ADDR).map(Command::Serve),
+        Some("grpc") => parse_serve_args(args, "grpc", DEFAULT_GRPC_ADDR).map(Command::Grpc),
+        Some("mcp") => match args.next() {
+            None => Ok(Command::Mcp),
+            Some(other) => Err(anyhow!("mcp 不支持的参数: {}", other)),
+        },
+        Some("rpc") => match args.next() {
+            None => Ok(Command::Rpc),
+            Some(other) => Err(anyhow!("rpc 不支持的参数: {}", other)),
+        },
+        Some(other) => Err(anyhow!("未知命令: {}", other)),
+    }
+}
+
+fn parse_tags_args<I: Iterator<Item = String>>(mut args: I) -> Result<TagsArgs> {
+    let mut tags_args = TagsArgs::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--limit" => {
+                let value = next_value(&mut args, "--limit")?;
+                tags_args.limit = value.parse().map_err(|_| anyhow!("--limit 需要一个数字，收到: {}", value))?;
+            }
+            "--prefix" => tags_args.prefix = next_value(&mut args, "--prefix")?,
+            "--of" => tags_args.of = Some(next_value(&mut args, "--of")?),
+            "--json" => tags_args.json = true,
+            other => return Err(anyhow!("tags 不支持的参数: {}", other)),
+        }
+    }
+    Ok(tags_args)
+}
+
+fn parse_doctor_args<I: Iterator<Item = String>>(args: I) -> Result<Command> {
+    let mut deep = false;
+    for arg in args {
+        match arg.as_str() {
+            "--deep" => deep = true,
+            other => return Err(anyhow!("doctor 不支持的参数: {}", other)),
+        }
+    }
+    Ok(Command::Doctor { deep })
+}
+
+fn parse_config_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command> {
+    match args.next().as_deref() {
+        Some("show") => {}
+        Some(other) => return Err(anyhow!("未知的 config 子命令: {} (目前只有 config show)", other)),
+        None => return Err(anyhow!("config 缺少子命令 (目前只有 config show)")),
+    }
+
+    let mut defaults = false;
+    for arg in args {
+        match arg.as_str() {
+            // 默认就是 --effective：配置文件 + 命令行覆盖 + 数值修正后的最终配置
+            "--effective" => defaults = false,
+            "--defaults" => defaults = true,
+            other => return Err(anyhow!("config show 不支持的参数: {}", other)),
+        }
+    }
+    Ok(Command::ConfigShow { defaults })
+}
+
+// serve 和 grpc 的参数相同，只是默认地址不同
+fn parse_serve_args<I: Iterator<Item = String>>(mut args: I, command: &str, default_addr: &str) -> Result<ServeArgs> {
+    let mut addr = default_addr.to_string();
+    let mut token = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--addr" => addr = next_value(&mut args, "--addr")?,
+            "--token" => token = Some(next_value(&mut args, "--token")?),
+            other => return Err(anyhow!("{} 不支持的参数: {}", command, other)),
+        }
+    }
+    let addr = addr.parse().map_err(|_| anyhow!("--addr 需要 IP:端口 格式，如 {}，收到: {}", default_addr, addr))?;
+    Ok(ServeArgs { addr, token })
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String> {
+    args.next().ok_or_else(|| anyhow!("{} 缺少参数值", flag))
+}
+
+// 执行 tags 命令，返回要打印的文本
+pub fn run_tags(index: &Index, args: &TagsArgs) -> Result<String> {
+    if let Some(path) = &args.of {
+        // 索引里存的是扫描时的路径 (如 "./docs/a.txt")，用户可能省略或多写 "./"
+        let mut found = None;
+        for candidate in path_candidates(path) {
+            found = get_document(index, &candidate)?;
+            if found.is_some() {
+                break;
+            }
+        }
+        let doc = found.ok_or_else(|| anyhow!("索引中没有这个文件: {}", path))?;
+        return Ok(if args.json { render_doc_tags_json(&doc) } else { render_doc_tags(&doc) });
+    }
+
+    let tags = list_tags(index, &args.prefix, args.limit)?;
+    Ok(if args.json { render_tags_json(&tags) } else { render_tags_table(&tags) })
+}
+
+fn path_candidates(path: &str) -> Vec<String> {
+    let mut candidates = vec![path.to_string()];
+    match path.strip_prefix("./") {
+        Some(stripped) => candidates.push(stripped.to_string()),
+        None => candidates.push(format!("./{}", path)),
+    }
+    candidates
+}
+
+// 按显示宽度右侧补空格 (中文字符占两列)
+fn pad_to_width(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(s.width());
+    format!("{}{}", s, " ".repeat(padding))
+}
+
+pub fn render_tags_table(tags: &[(String, usize)]) -> String {
+    if tags.is_empty() {
+        return "没有找到标签".to_string();
+    }
+
+    let tag_header = "标签";
+    let count_header = "文档数";
+    let tag_width = tags.iter().map(|(tag, _)| tag.width()).max().unwrap_or(0).max(tag_header.width());
+    let count_width = tags.iter().map(|(_, count)| count.to_string().len()).max().unwrap_or(0).max(count_header.width());
+
+    let mut lines = Vec::with_capacity(tags.len() + 1);
+    lines.push(format!("{}  {}", pad_to_width(tag_header, tag_width), count_header));
+    for (tag, count) in tags {
+        lines.push(format!("{}  {:>count_width$}", pad_to_width(tag, tag_width), count));
+    }
+    lines.join("\n")
+}
+
+pub fn render_tags_json(tags: &[(String, usize)]) -> String {
+    let items: Vec<_> = tags.iter().map(|(tag, count)| json!({ "tag": tag, "count": count })).collect();
+    serde_json::to_string_pretty(&items).unwrap_or_default()
+}
+
+pub fn render_doc_tags(doc: &StoredDoc) -> String {
+    let tags = if doc.tags.is_empty() { "[无标签]".to_string() } else { doc.tags.join(" ") };
+    format!(" [文档标题] {}\n    路径: {}\n    标签: {}", doc.title, doc.path, tags)
+}
+
+pub fn render_doc_tags_json(doc: &StoredDoc) -> String {
+    let value = json!({ "path": doc.path, "title": doc.title, "tags": doc.tags });
+    serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::tests::tagged_index;
+
+    fn tags_args(args: &[&str]) -> TagsArgs {
+        parse_tags_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn tags_command_lists_limits_and_filters() {
+        let index = tagged_index();
+
+        let table = run_tags(&index, &tags_args(&["--limit", "2"])).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", table);
+        assert!(lines[1].starts_with("机器学习") && lines[1].ends_with('3'));
+
+        let json: serde_json::Value = serde_json::from_str(&run_tags(&index, &tags_args(&["--prefix", "机器", "--json"])).unwrap()).unwrap();
+        assert_eq!(json, json!([{ "tag": "机器学习", "count": 3 }, { "tag": "机器人", "count": 2 }]));
+
+        assert_eq!(run_tags(&index, &tags_args(&["--prefix", "没有"])).unwrap(), "没有找到标签");
+    }
+
+    #[test]
+    fn tags_of_accepts_paths_with_or_without_dot_slash() {
+        let index = tagged_index();
+        for path in ["./docs/b.txt", "docs/b.txt"] {
+            let json: serde_json::Value = serde_json::from_str(&run_tags(&index, &tags_args(&["--of", path, "--json"])).unwrap()).unwrap();
+            assert_eq!(json["path"], "./docs/b.txt");
+            assert_eq!(json["tags"], json!(["机器学习", "机器人"]));
+        }
+        assert!(run_tags(&index, &tags_args(&["--of", "./docs/d.md"])).unwrap().contains("[无标签]"));
+        assert!(run_tags(&index, &tags_args(&["--of", "missing.txt"])).is_err());
+    }
+
+    #[test]
+    fn tags_table_aligns_cjk_columns() {
+        let tags = [("机器学习".to_string(), 120), ("rust".to_string(), 7), ("神经".to_string(), 12)];
+        let table = render_tags_table(&tags);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "标签      文档数");
+        // 中文按两列算宽度，每行显示宽度相同、数字右对齐
+        assert!(lines.iter().all(|line| line.width() == lines[0].width()), "{}", table);
+        assert_eq!(lines[1], "机器学习     120");
+        assert_eq!(lines[2], "rust           7");
+        assert_eq!(lines[3], "神经          12");
+    }
+
+    #[test]
+    fn tags_arguments_are_validated() {
+        assert_eq!(tags_args(&[]), TagsArgs::default());
+        assert!(parse_tags_args(["--limit", "ten"].into_iter().map(String::from)).is_err());
+        assert!(parse_tags_args(["--limit"].into_iter().map(String::from)).is_err());
+        assert!(parse_tags_args(["--verbose"].into_iter().map(String::from)).is_err());
+    }
+
+    fn parse(args: &[&str]) -> Cli {
+        parse_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn global_flags_only_work_before_the_subcommand() {
+        let expected = Cli {
+            config_path: Some(PathBuf::from("my.toml")),
+            overrides: Overrides { no_ai: true, model_path: Some(PathBuf::from("/models")), model_id: None },
+            command: Command::Tags(TagsArgs { limit: 5, ..TagsArgs::default() }),
+        };
+        assert_eq!(parse(&["--no-ai", "--config", "my.toml", "--model-path", "/models", "tags", "--limit", "5"]), expected);
+
+        // 子命令后面的全局选项名交给子命令解析，可以当作参数值
+        let cli = parse(&["tags", "--prefix", "--no-ai"]);
+        assert_eq!(cli.overrides, Overrides::default());
+        assert_eq!(cli.command, Command::Tags(TagsArgs { prefix: "--no-ai".to_string(), ..TagsArgs::default() }));
+
+        let error = parse_args(["tags", "--limit", "5", "--no-ai"].map(String::from)).unwrap_err().to_string();
+        assert!(error.contains("tags 不支持的参数: --no-ai") && error.contains("要写在子命令前面"), "{}", error);
+    }
+
+    #[test]
+    fn commands_and_their_arguments() {
+        assert_eq!(parse(&[]).command, Command::Interactive);
+        assert_eq!(parse(&["--no-ai"]), Cli { config_path: None, overrides: Overrides { no_ai: true, ..Overrides::default() }, command: Command::Interactive });
+        assert_eq!(parse(&["doctor", "--deep"]).command, Command::Doctor { deep: true });
+        assert_eq!(parse(&["config", "show", "--defaults"]).command, Command::ConfigShow { defaults: true });
+        assert_eq!(
+            parse(&["--model-id", "Xenova/bge-small-zh-v1.5", "serve", "--token", "t"]),
+            Cli {
+                config_path: None,
+                overrides: Overrides { model_id: Some("Xenova/bge-small-zh-v1.5".to_string()), ..Overrides::default() },
+                command: Command::Serve(ServeArgs { addr: DEFAULT_SERVE_ADDR.parse().unwrap(), token: Some("t".to_string()) }),
+            }
+        );
+        assert_eq!(parse(&["grpc"]).command, Command::Grpc(ServeArgs { addr: DEFAULT_GRPC_ADDR.parse().unwrap(), token: None }));
+
+        for bad in [&["search"][..], &["--config"], &["serve", "--addr", "7700"], &["tui", "--fast"], &["config"]] {
+            assert!(parse_args(bad.iter().map(|arg| arg.to_string())).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_search.toml");
+        std::fs::write(&path, "[ai]\nenabled = true\nmodel_path = \"/from/file\"\nmodel_id = \"from-file\"\ntags_per_doc = 4\n").unwrap();
+
+        let config_arg = path.to_string_lossy().into_owned();
+        let cli = parse(&["--config", &config_arg, "--no-ai", "--model-path", "/from/cli"]);
+        let config = cli.load_config().unwrap();
+        assert!(!config.ai.enabled);
+        assert_eq!(config.ai.model_path, Some(PathBuf::from("/from/cli")));
+        // 命令行没给的保持配置文件里的值
+        assert_eq!(config.ai.model_id.as_deref(), Some("from-file"));
+        assert_eq!(config.ai.tags_per_doc, 4);
+
+        // 显式指定的配置文件必须存在
+        let missing = dir.path().join("missing.toml").to_string_lossy().into_owned();
+        assert!(parse(&["--config", &missing]).load_config().is_err());
+    }
+}
@@ -1,5 +1,1046 @@
 // 配置常量
 pub const PREVIEW_MAX_LENGTH: usize = 200;        // 内容预览的最大字符数
 pub const SENTENCE_SEARCH_START: usize = 50;      // 句子搜索的起始位置
-pub const WATCH_PATH: &str = "./docs";             // 监控目录路径
-pub const STORAGE_PATH: &str = "./storage";        // 索引存储路径
\ No newline at end of file
+pub const WATCH_PATH: &str = "./docs";             // 默认监控目录路径
+pub const STORAGE_PATH: &str = "./storage";        // 默认索引存储路径
+pub const CONFIG_PATH: &str = "./ai_search.toml";  // 默认配置文件路径
+pub const WRITER_HEAP_SIZE: usize = 50_000_000;    // 默认 IndexWriter 内存预算
+pub const MIN_WRITER_HEAP_SIZE: usize = 15_000_000; // tantivy 要求每个写入线程至少 15MB
+pub const MAX_WRITER_HEAP_SIZE: usize = 4_000_000_000; // 单个写入线程的上限约 4GB
+pub const COMMIT_EVERY_DOCS: usize = 50;           // 扫描时每处理多少个文件提交一次
+pub const TAGS_PER_DOC: usize = 3;                 // 每个文档生成的标签数
+pub const MAX_FILE_SIZE: u64 = 50 * 1024 * 1024;   // 超过这个大小的文件不索引
+pub const DEBOUNCE_MS: u64 = 500;                  // 监控到文件变化后等待写入完成的时间
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use anyhow::{Context, Result, anyhow};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::extract::SUPPORTED_EXTENSIONS;
+
+// 配置文件 (TOML) 的完整结构，缺省的段落和字段都使用上面的默认值：
+//
+// [index]
+// storage_path = "./storage"
+// heap_size = 50000000
+// commit_every_docs = 50
+//
+// [ai]
+// enabled = true
+// model_path = "/path/to/model"          # 可选，本地模型目录，不会自动下载
+// model_id = "Xenova/bge-small-zh-v1.5"  # 可选
+// tags_per_doc = 3
+//
+// [scan]
+// parallelism = 4             # 初始扫描时并行提取文本的线程数，默认取 CPU 核数 (最多 8)
+// inference_parallelism = 1   # 同时跑模型推理的线程数，内存小的机器保持 1
+//
+// [watch]
+// roots = ["./docs"]                     # 也可以用 [[watch.roots]] 给单个目录写覆盖，见 RootConfig
+// extensions = ["txt", "md", "pdf"]
+// excludes = [".DS_Store"]
+// max_file_size = 52428800
+// debounce_ms = 500
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub index: IndexConfig,
+    pub ai: AiConfig,
+    pub scan: ScanConfig,
+    pub watch: WatchConfig,
+    // 配置文件里不认识的键 (如 "watch.exclude")，load_from 时记下来，由 validate 报告
+    #[serde(skip)]
+    pub unknown_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    pub storage_path: PathBuf,
+    pub heap_size: usize,
+    pub commit_every_docs: usize,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            storage_path: PathBuf::from(STORAGE_PATH),
+            heap_size: WRITER_HEAP_SIZE,
+            commit_every_docs: COMMIT_EVERY_DOCS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiConfig {
+    pub enabled: bool,
+    pub model_path: Option<PathBuf>, // 本地模型目录 (布局同 Hugging Face 仓库)，指定后只从这里加载、不联网下载
+    pub model_id: Option<String>,    // Hugging Face 上的模型名，如 "Xenova/bge-small-zh-v1.5"
+    pub tags_per_doc: usize,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self { enabled: true, model_path: None, model_id: None, tags_per_doc: TAGS_PER_DOC }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    pub parallelism: usize,
+    pub inference_parallelism: usize,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self { parallelism: cpus.min(8), inference_parallelism: 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    pub roots: Vec<RootConfig>,
+    pub extensions: Vec<String>,
+    pub excludes: Vec<String>, // 路径里包含这些片段的文件会被跳过
+    pub max_file_size: u64,
+    pub debounce_ms: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            roots: vec![RootConfig::new(WATCH_PATH)],
+            extensions: vec!["txt".to_string(), "md".to_string(), "pdf".to_string()],
+            excludes: vec![".DS_Store".to_string()],
+            max_file_size: MAX_FILE_SIZE,
+            debounce_ms: DEBOUNCE_MS,
+        }
+    }
+}
+
+// 单个监控目录的设置，没写的字段沿用 [watch] / [ai] 里的全局值。
+// 配置文件里既可以直接写路径字符串，也可以写成表：
+//
+// [[watch.roots]]
+// path = "./code"
+// extensions = ["rs", "md"]
+// exclude = ["/target/"]
+// ai_tags = false
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RootEntry")]
+pub struct RootConfig {
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<String>>, // 替换全局的扩展名白名单
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>, // 追加在全局 excludes 之后
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ai_tags: Option<bool>, // 是否用 AI 生成标签，AI 整体禁用时设为 true 也不会生效
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+}
+
+impl RootConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), extensions: None, exclude: Vec::new(), ai_tags: None, max_file_size: None }
+    }
+
+    // 是否覆盖了任何全局设置；嵌套在别的监控目录里又没有覆盖时，这个目录没有意义
+    fn has_overrides(&self) -> bool {
+        self.extensions.is_some() || !self.exclude.is_empty() || self.ai_tags.is_some() || self.max_file_size.is_some()
+    }
+}
+
+// 配置文件里的一项监控目录：路径字符串或表。
+// 不用 #[serde(untagged)]：那样表里某个字段类型写错时只会报 "did not match any variant"，看不出是哪个字段
+enum RootEntry {
+    Path(PathBuf),
+    Table(RootTable),
+}
+
+#[derive(Deserialize)]
+struct RootTable {
+    path: PathBuf,
+    #[serde(default)]
+    extensions: Option<Vec<String>>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    ai_tags: Option<bool>,
+    #[serde(default)]
+    max_file_size: Option<u64>,
+}
+
+impl<'de> Deserialize<'de> for RootEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = RootEntry;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("监控目录的路径字符串，或带 path 字段的表")
+            }
+
+            fn visit_str<E: de::Error>(self, path: &str) -> std::result::Result<RootEntry, E> {
+                Ok(RootEntry::Path(PathBuf::from(path)))
+            }
+
+            // 表的字段错误原样报出来，带上具体的字段名
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<RootEntry, A::Error> {
+                RootTable::deserialize(MapAccessDeserializer::new(map)).map(RootEntry::Table)
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+impl From<RootEntry> for RootConfig {
+    fn from(entry: RootEntry) -> Self {
+        match entry {
+            RootEntry::Path(path) => RootConfig::new(path),
+            RootEntry::Table(RootTable { path, extensions, exclude, ai_tags, max_file_size }) => {
+                RootConfig { path, extensions, exclude, ai_tags, max_file_size }
+            }
+        }
+    }
+}
+
+impl WatchConfig {
+    pub fn root_paths(&self) -> Vec<PathBuf> {
+        self.roots.iter().map(|root| root.path.clone()).collect()
+    }
+
+    // 把任意写法的路径 (绝对路径、不同的相对路径) 换成扫描时的写法 "<监控目录>/<相对路径>"，
+    // 索引里同一个文件才不会出现两份；不在任何监控目录下或文件不存在时返回 None。
+    // 外部请求的路径不可信：两边都先 canonicalize 解析掉 ".." 和符号链接，再比较前缀，
+    // 否则 "docs/../secret" 或指向目录外的链接都能绕过检查
+    pub fn normalize_path(&self, path: &Path) -> Option<PathBuf> {
+        let canonical = fs::canonicalize(path).ok()?;
+        let (root, canonical_root) = self
+            .roots
+            .iter()
+            .filter_map(|root| Some((root, fs::canonicalize(&root.path).ok()?)))
+            .filter(|(_, canonical_root)| canonical.starts_with(canonical_root))
+            .max_by_key(|(_, canonical_root)| canonical_root.components().count())?;
+        let relative = canonical.strip_prefix(&canonical_root).ok()?;
+        Some(root.path.join(relative))
+    }
+
+    // 找到包含 path 的监控目录；目录嵌套时取最长 (最具体) 的那个，不在任何目录下返回 None
+    pub fn root_for(&self, path: &Path) -> Option<&RootConfig> {
+        let path = absolute(path);
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(absolute(&root.path)))
+            .max_by_key(|root| absolute(&root.path).components().count())
+    }
+}
+
+// 某个文件实际生效的过滤和打标签规则 (全局设置叠加所属监控目录的覆盖)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRules {
+    pub extensions: Vec<String>,
+    pub excludes: Vec<String>,
+    pub ai_tags: bool,
+    pub max_file_size: u64,
+}
+
+impl FileRules {
+    // 扫描和监控共用的过滤规则：扩展名白名单、排除路径、文件大小
+    pub fn accepts(&self, path: &Path) -> bool {
+        let Some(ext) = path.extension().map(|e| e.to_string_lossy().to_lowercase()) else {
+            return false;
+        };
+        if !self.extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(&ext)) {
+            return false;
+        }
+
+        let path_str = path.to_string_lossy();
+        if self.excludes.iter().any(|pattern| path_str.contains(pattern.as_str())) {
+            return false;
+        }
+
+        fs::metadata(path).map(|m| m.len() <= self.max_file_size).unwrap_or(false)
+    }
+}
+
+// 判断两个监控目录是不是同一个目录：存在时按 canonicalize 的结果比较 (不同写法、符号链接都算同一个)
+fn same_dir(a: &Path, b: &Path) -> bool {
+    let resolve = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| absolute(path));
+    resolve(a) == resolve(b)
+}
+
+// 比较路径前缀前先转成绝对路径，"./docs" 和 watcher 报上来的绝对路径才能对上；
+// ".." 按字面消掉 (文件可能已经被删了，不能 canonicalize)，"docs/../x" 不会被算进 docs
+fn absolute(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                cleaned.pop();
+            }
+            Component::CurDir => {}
+            other => cleaned.push(other),
+        }
+    }
+    cleaned
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemLevel {
+    Warning, // 可以继续运行，但结果可能不符合预期
+    Error,   // 拒绝启动
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblem {
+    pub level: ProblemLevel,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn warning(message: impl Into<String>) -> Self {
+        Self { level: ProblemLevel::Warning, message: message.into() }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self { level: ProblemLevel::Error, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self.level {
+            ProblemLevel::Warning => "警告",
+            ProblemLevel::Error => "错误",
+        };
+        write!(f, "[{}] {}", label, self.message)
+    }
+}
+
+fn check_extensions(name: &str, extensions: &[String], problems: &mut Vec<ConfigProblem>) {
+    if extensions.is_empty() {
+        problems.push(ConfigProblem::warning(format!("{} 为空，不会索引任何文件", name)));
+    }
+    for ext in extensions.iter().filter(|ext| ext.starts_with('.')) {
+        problems.push(ConfigProblem::warning(format!("{} 中的 {:?} 不需要带点，应写成 {:?}", name, ext, ext.trim_start_matches('.'))));
+    }
+    // 过滤规则放行了但提取不了文本的文件，到索引时才会逐个报错
+    for ext in extensions.iter().filter(|ext| !SUPPORTED_EXTENSIONS.contains(&ext.trim_start_matches('.').to_lowercase().as_str())) {
+        problems.push(ConfigProblem::warning(format!(
+            "{} 中的 {:?} 没有对应的文本提取器，这类文件会被跳过 (支持: {})",
+            name,
+            ext,
+            SUPPORTED_EXTENSIONS.join(", ")
+        )));
+    }
+}
+
+// 找出配置文件里 Config 不认识的键 (serde 默认会直接忽略它们)，返回 "watch.exclude" 这样的完整键名
+fn unknown_keys(content: &str) -> Vec<String> {
+    let Ok(raw) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    // 用一份所有可选字段都填上的配置作为"已知键"的参照
+    let mut reference = Config::default();
+    reference.ai.model_path = Some(PathBuf::new());
+    reference.ai.model_id = Some(String::new());
+    reference.watch.roots = vec![RootConfig {
+        path: PathBuf::new(),
+        extensions: Some(Vec::new()),
+        exclude: vec![String::new()],
+        ai_tags: Some(true),
+        max_file_size: Some(0),
+    }];
+    let Ok(toml::Value::Table(known)) = toml::Value::try_from(&reference) else {
+        return Vec::new();
+    };
+
+    let mut unknown = Vec::new();
+    collect_unknown_keys("", &raw, &known, &mut unknown);
+    unknown
+}
+
+fn collect_unknown_keys(prefix: &str, raw: &toml::Table, known: &toml::Table, unknown: &mut Vec<String>) {
+    for (key, value) in raw {
+        let full_key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (value, known.get(key)) {
+            (_, None) => unknown.push(full_key),
+            (toml::Value::Table(raw), Some(toml::Value::Table(known))) => collect_unknown_keys(&full_key, raw, known, unknown),
+            // [[watch.roots]] 这样的表数组，逐个元素和参照的第一个元素比对
+            (toml::Value::Array(items), Some(toml::Value::Array(known_items))) => {
+                if let Some(toml::Value::Table(known)) = known_items.first() {
+                    for item in items {
+                        if let toml::Value::Table(raw) = item {
+                            collect_unknown_keys(&full_key, raw, known, unknown);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 运行中的配置句柄：读的一方用 current() 拿到一份快照 (Arc)，
+// reload 时整体替换，正在使用旧快照的代码不受影响。clone 出来的句柄共享同一份配置
+#[derive(Debug, Clone)]
+pub struct ConfigHandle(Arc<RwLock<Arc<Config>>>);
+
+impl ConfigHandle {
+    pub fn new(config: impl Into<Arc<Config>>) -> Self {
+        Self(Arc::new(RwLock::new(config.into())))
+    }
+
+    // 扩展名、排除路径等可以运行时修改的设置，每次用的时候都应该重新调用这里，不要缓存
+    pub fn current(&self) -> Arc<Config> {
+        self.0.read().unwrap().clone()
+    }
+
+    // 运行时替换配置；需要重启才能生效的设置有变化时整体拒绝，不做部分更新
+    pub fn reload(&self, new: Arc<Config>) -> Result<()> {
+        let mut current = self.0.write().unwrap();
+        let changed = current.restart_required_changes(&new);
+        if !changed.is_empty() {
+            return Err(anyhow!("以下配置需要重启才能生效，本次重新加载已取消: {}", changed.join(", ")));
+        }
+        *current = new;
+        Ok(())
+    }
+}
+
+// 进程里共用的配置句柄：main 启动时用 set_global 登记，和 AppState 持有的是同一个句柄，
+// 所以 reload 之后 Config::global() 拿到的也是新配置
+static GLOBAL: OnceLock<ConfigHandle> = OnceLock::new();
+
+impl Config {
+    // 当前的全局配置快照；没登记过句柄时按默认路径加载
+    pub fn global() -> Arc<Config> {
+        Config::global_handle().current()
+    }
+
+    pub fn global_handle() -> &'static ConfigHandle {
+        GLOBAL.get_or_init(|| {
+            let config = Config::load_or_default(Path::new(CONFIG_PATH)).unwrap_or_else(|e| {
+                eprintln!(" [配置] {}，使用默认配置", e);
+                Config::default()
+            });
+            ConfigHandle::new(config)
+        })
+    }
+
+    pub fn set_global(handle: ConfigHandle) -> Result<()> {
+        GLOBAL.set(handle).map_err(|_| anyhow!("全局配置已经初始化过了"))
+    }
+
+    // 解析 path 所属监控目录，得到实际生效的规则；不在任何监控目录下的文件使用全局设置
+    pub fn rules_for(&self, path: &Path) -> FileRules {
+        let watch = &self.watch;
+        let root = watch.root_for(path);
+
+        let mut excludes = watch.excludes.clone();
+        if let Some(root) = root {
+            excludes.extend(root.exclude.iter().cloned());
+        }
+
+        FileRules {
+            extensions: root.and_then(|r| r.extensions.clone()).unwrap_or_else(|| watch.extensions.clone()),
+            excludes,
+            ai_tags: self.ai.enabled && root.and_then(|r| r.ai_tags).unwrap_or(true),
+            max_file_size: root.and_then(|r| r.max_file_size).unwrap_or(watch.max_file_size),
+        }
+    }
+
+    // 列出和 new 相比有变化、且运行中无法切换的字段：
+    // 存储路径和模型决定了已打开的索引和已加载的模型，监控目录决定了已注册的 watcher，
+    // 写入内存和扫描线程数只在启动时的初始扫描里读一次
+    pub fn restart_required_changes(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.index.storage_path != new.index.storage_path {
+            changed.push("index.storage_path");
+        }
+        if self.index.heap_size != new.index.heap_size {
+            changed.push("index.heap_size");
+        }
+        if self.scan.parallelism != new.scan.parallelism {
+            changed.push("scan.parallelism");
+        }
+        if self.scan.inference_parallelism != new.scan.inference_parallelism {
+            changed.push("scan.inference_parallelism");
+        }
+        if self.ai.enabled != new.ai.enabled {
+            changed.push("ai.enabled");
+        }
+        if self.ai.model_path != new.ai.model_path {
+            changed.push("ai.model_path");
+        }
+        if self.ai.model_id != new.ai.model_id {
+            changed.push("ai.model_id");
+        }
+        // 每个目录的过滤规则可以热更新，只有目录本身的增删需要重新注册 watcher
+        if self.watch.root_paths() != new.watch.root_paths() {
+            changed.push("watch.roots");
+        }
+        changed
+    }
+
+    // 读取并解析配置文件，文件不存在或格式错误都会报错；
+    // 拼错的键记在 unknown_keys 里由 validate 报告，超出安全范围的数值打印警告并修正
+    pub fn load_from(path: &Path) -> Result<Config> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("无法读取配置文件 {:?}", path))?;
+        let mut config: Config = toml::from_str(&content).map_err(|e| anyhow!("配置文件 {:?} 格式错误: {}", path, e))?;
+        config.unknown_keys = unknown_keys(&content);
+        for warning in config.clamp_to_safe_range() {
+            eprintln!(" [配置] {}", warning);
+        }
+        Ok(config)
+    }
+
+    // 检查配置本身是否合理 (路径、目录、大小等)，返回发现的所有问题
+    pub fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+
+        let storage = &self.index.storage_path;
+        if storage.exists() && !storage.is_dir() {
+            problems.push(ConfigProblem::error(format!("index.storage_path {:?} 不是目录", storage)));
+        } else if fs::metadata(storage).is_ok_and(|m| m.permissions().readonly()) {
+            problems.push(ConfigProblem::error(format!("index.storage_path {:?} 不可写", storage)));
+        }
+
+        let watch = &self.watch;
+        if watch.roots.is_empty() {
+            problems.push(ConfigProblem::error("watch.roots 为空，没有任何目录可以索引"));
+        }
+        for (i, root) in watch.roots.iter().enumerate() {
+            let path = &root.path;
+            if path.exists() && !path.is_dir() {
+                problems.push(ConfigProblem::error(format!("监控目录 {:?} 不是目录", path)));
+            } else if !path.exists() {
+                problems.push(ConfigProblem::warning(format!("监控目录 {:?} 不存在，启动时会自动创建", path)));
+            }
+            // 同一个目录 (包括不同写法、符号链接) 配了两次是错误；
+            // 嵌套的目录是允许的 (内层覆盖外层)，但内层什么都不覆盖时只是和外层重复
+            if let Some(other) = watch.roots[..i].iter().find(|other| same_dir(&other.path, path)) {
+                problems.push(ConfigProblem::error(format!("监控目录 {:?} 和 {:?} 是同一个目录，重复配置", path, other.path)));
+            } else if !root.has_overrides()
+                && let Some(outer) = watch.roots.iter().find(|outer| !same_dir(&outer.path, path) && absolute(path).starts_with(absolute(&outer.path)))
+            {
+                problems.push(ConfigProblem::warning(format!("监控目录 {:?} 在 {:?} 里面，又没有覆盖任何设置，可以去掉", path, outer.path)));
+            }
+            if root.max_file_size == Some(0) {
+                problems.push(ConfigProblem::error(format!("监控目录 {:?} 的 max_file_size 为 0，不会索引任何文件", path)));
+            }
+            if let Some(extensions) = &root.extensions {
+                check_extensions(&format!("监控目录 {:?} 的 extensions", path), extensions, &mut problems);
+            }
+        }
+
+        if watch.max_file_size == 0 {
+            problems.push(ConfigProblem::error("watch.max_file_size 为 0，不会索引任何文件"));
+        }
+        check_extensions("watch.extensions", &watch.extensions, &mut problems);
+
+        for key in &self.unknown_keys {
+            problems.push(ConfigProblem::warning(format!("未知的配置项 {}，已忽略 (是否拼错了?)", key)));
+        }
+
+        problems
+    }
+
+    // 校验配置：警告打印出来，有错误时拒绝使用这份配置
+    pub fn validated(self) -> Result<Config> {
+        let problems = self.validate();
+        let errors: Vec<String> = problems
+            .iter()
+            .filter(|p| p.level == ProblemLevel::Error)
+            .map(|p| p.message.clone())
+            .collect();
+        for problem in problems.iter().filter(|p| p.level == ProblemLevel::Warning) {
+            eprintln!(" [配置] {}", problem);
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!("配置有误:\n  {}", errors.join("\n  ")));
+        }
+        Ok(self)
+    }
+
+    // 序列化成 TOML (config show 用)，输出可以直接保存为配置文件
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| anyhow!("配置无法序列化为 TOML: {}", e))
+    }
+
+    // 把调优参数限制在安全范围内，返回每一项修正的说明
+    pub fn clamp_to_safe_range(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        clamp("index.heap_size", &mut self.index.heap_size, MIN_WRITER_HEAP_SIZE, MAX_WRITER_HEAP_SIZE, &mut warnings);
+        clamp("index.commit_every_docs", &mut self.index.commit_every_docs, 1, 100_000, &mut warnings);
+        clamp("ai.tags_per_doc", &mut self.ai.tags_per_doc, 1, 20, &mut warnings);
+        clamp("scan.parallelism", &mut self.scan.parallelism, 1, 64, &mut warnings);
+        // 推理线程多于扫描线程没有意义
+        let max_inference = self.scan.parallelism;
+        clamp("scan.inference_parallelism", &mut self.scan.inference_parallelism, 1, max_inference, &mut warnings);
+        clamp("watch.debounce_ms", &mut self.watch.debounce_ms, 0, 10_000, &mut warnings);
+        warnings
+    }
+
+    // 配置文件不存在时使用默认配置
+    pub fn load_or_default(path: &Path) -> Result<Config> {
+        if path.exists() {
+            Config::load_from(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+}
+
+fn clamp<T: PartialOrd + Copy + std::fmt::Display>(name: &str, value: &mut T, min: T, max: T, warnings: &mut Vec<String>) {
+    let clamped = if *value < min { min } else if *value > max { max } else { *value };
+    if clamped != *value {
+        warnings.push(format!("{} = {} 超出范围 [{}, {}]，已改为 {}", name, value, min, max, clamped));
+        *value = clamped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_replaces_hot_settings_for_every_clone() {
+        let handle = ConfigHandle::new(Config::default());
+        let shared = handle.clone();
+
+        let mut new = Config::default();
+        new.watch.extensions = vec!["rs".to_string()];
+        new.watch.debounce_ms = 0;
+        handle.reload(Arc::new(new.clone())).unwrap();
+        assert_eq!(*shared.current(), new);
+    }
+
+    #[test]
+    fn reload_rejects_restart_required_changes() {
+        let handle = ConfigHandle::new(Config::default());
+
+        let mut new = Config::default();
+        new.watch.extensions = vec!["rs".to_string()];
+        new.watch.roots.push(RootConfig::new("./other"));
+        new.index.storage_path = PathBuf::from("./elsewhere");
+        let error = handle.reload(Arc::new(new)).unwrap_err().to_string();
+        assert!(error.contains("watch.roots") && error.contains("index.storage_path"), "{}", error);
+        // 整体拒绝，可以热更新的字段也保持原样
+        assert_eq!(*handle.current(), Config::default());
+    }
+
+    #[test]
+    fn global_follows_reloads_of_the_registered_handle() {
+        let handle = ConfigHandle::new(Config::default());
+        Config::set_global(handle.clone()).unwrap();
+        assert!(Config::set_global(ConfigHandle::new(Config::default())).is_err());
+
+        let mut new = Config::default();
+        new.watch.extensions = vec!["rs".to_string()];
+        handle.reload(Arc::new(new.clone())).unwrap();
+        assert_eq!(*Config::global(), new);
+    }
+
+    #[test]
+    fn reload_rejects_scan_knobs_read_only_at_startup() {
+        let handle = ConfigHandle::new(Config::default());
+
+        let mut new = Config::default();
+        new.index.heap_size += MIN_WRITER_HEAP_SIZE;
+        new.scan.parallelism += 1;
+        new.scan.inference_parallelism += 1;
+        let error = handle.reload(Arc::new(new)).unwrap_err().to_string();
+        for key in ["index.heap_size", "scan.parallelism", "scan.inference_parallelism"] {
+            assert!(error.contains(key), "{}", error);
+        }
+
+        // 提交间隔每个文件都会重新读取，可以热更新
+        let mut new = Config::default();
+        new.index.commit_every_docs += 1;
+        handle.reload(Arc::new(new)).unwrap();
+    }
+
+    // 全局 txt/md，code 目录换成 rs 且不打标签，code/vendor 再单独放宽大小
+    const NESTED_ROOTS: &str = r#"
+        [watch]
+        extensions = ["txt", "md"]
+        excludes = [".DS_Store"]
+        max_file_size = 100
+
+        [[watch.roots]]
+        path = "/data/code"
+        extensions = ["rs"]
+        exclude = ["/target/"]
+        ai_tags = false
+
+        [[watch.roots]]
+        path = "/data/code/vendor"
+        max_file_size = 1000
+
+        [[watch.roots]]
+        path = "/data/docs"
+    "#;
+
+    #[test]
+    fn root_for_picks_the_innermost_root() {
+        let config: Config = toml::from_str(NESTED_ROOTS).unwrap();
+        let watch = &config.watch;
+        let root_of = |path: &str| watch.root_for(Path::new(path)).map(|root| root.path.clone());
+
+        assert_eq!(root_of("/data/code/src/main.rs"), Some(PathBuf::from("/data/code")));
+        assert_eq!(root_of("/data/code/vendor/lib.rs"), Some(PathBuf::from("/data/code/vendor")));
+        assert_eq!(root_of("/data/docs/a.md"), Some(PathBuf::from("/data/docs")));
+        // 前缀相同但不是子目录、以及用 .. 绕出去的路径都不算
+        assert_eq!(root_of("/data/docs2/a.md"), None);
+        assert_eq!(root_of("/data/docs/../secret.md"), None);
+    }
+
+    #[test]
+    fn rules_for_layers_root_overrides_on_global_settings() {
+        let config: Config = toml::from_str(NESTED_ROOTS).unwrap();
+
+        let code = config.rules_for(Path::new("/data/code/src/main.rs"));
+        assert_eq!(code.extensions, ["rs"]);
+        assert_eq!(code.excludes, [".DS_Store", "/target/"]);
+        assert_eq!((code.ai_tags, code.max_file_size), (false, 100));
+
+        // 内层目录只覆盖自己写了的字段，其余沿用全局值而不是外层目录的
+        let vendor = config.rules_for(Path::new("/data/code/vendor/lib.rs"));
+        assert_eq!(vendor.extensions, ["txt", "md"]);
+        assert_eq!((vendor.ai_tags, vendor.max_file_size), (true, 1000));
+
+        let docs = config.rules_for(Path::new("/data/docs/a.md"));
+        assert_eq!(docs, config.rules_for(Path::new("/elsewhere/a.md")));
+        assert_eq!(docs.excludes, [".DS_Store"]);
+    }
+
+    #[test]
+    fn ai_tags_per_root_respects_global_switch() {
+        let mut config: Config = toml::from_str(NESTED_ROOTS).unwrap();
+        assert!(config.rules_for(Path::new("/data/docs/a.md")).ai_tags);
+        assert!(!config.rules_for(Path::new("/data/code/main.rs")).ai_tags);
+
+        // AI 整体关闭时，目录里写 ai_tags = true 也不生效
+        config.ai.enabled = false;
+        config.watch.roots[2].ai_tags = Some(true);
+        assert!(!config.rules_for(Path::new("/data/docs/a.md")).ai_tags);
+    }
+
+    #[test]
+    fn root_entries_accept_strings_and_tables() {
+        let config: Config = toml::from_str(
+            r#"
+            [watch]
+            roots = ["./docs", { path = "./code", ai_tags = false }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.watch.roots[0], RootConfig::new("./docs"));
+        assert_eq!(config.watch.roots[1].ai_tags, Some(false));
+    }
+
+    #[test]
+    fn root_table_errors_name_the_bad_field() {
+        let error = toml::from_str::<Config>(
+            r#"
+            [[watch.roots]]
+            path = "./code"
+            ai_tags = "no"
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("invalid type: string \"no\", expected a boolean"), "{}", error);
+        assert!(!error.contains("untagged"), "{}", error);
+
+        let error = toml::from_str::<Config>("[[watch.roots]]\nextensions = [\"rs\"]\n").unwrap_err().to_string();
+        assert!(error.contains("missing field `path`"), "{}", error);
+    }
+
+    #[test]
+    fn extensions_without_an_extractor_are_warned() {
+        let mut config = Config::default();
+        config.watch.extensions = vec!["md".to_string(), "PDF".to_string(), "docx".to_string()];
+        config.watch.roots[0].extensions = Some(vec!["rs".to_string(), "py".to_string()]);
+
+        let warnings: Vec<String> = config
+            .validate()
+            .into_iter()
+            .filter(|problem| problem.level == ProblemLevel::Warning && problem.message.contains("没有对应的文本提取器"))
+            .map(|problem| problem.message)
+            .collect();
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[0].contains("\"py\""));
+        assert!(warnings[1].contains("watch.extensions") && warnings[1].contains("\"docx\""));
+    }
+
+    // 存储目录和监控目录都建在临时目录里，本身没有任何问题的配置
+    fn clean_config(dir: &Path) -> Config {
+        fs::create_dir_all(dir.join("storage")).unwrap();
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        let mut config = Config::default();
+        config.index.storage_path = dir.join("storage");
+        config.watch.roots = vec![RootConfig::new(dir.join("docs"))];
+        assert_eq!(config.validate(), []);
+        config
+    }
+
+    // 只有一条问题时返回它的级别和说明
+    fn single_problem(config: &Config) -> (ProblemLevel, String) {
+        let problems = config.validate();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        (problems[0].level, problems[0].message.clone())
+    }
+
+    #[test]
+    fn validate_storage_path_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = clean_config(dir.path());
+
+        fs::write(dir.path().join("file"), "").unwrap();
+        config.index.storage_path = dir.path().join("file");
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Error && message.contains("不是目录"), "{}", message);
+
+        let storage = dir.path().join("storage");
+        let mut permissions = fs::metadata(&storage).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&storage, permissions).unwrap();
+        config.index.storage_path = storage;
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Error && message.contains("不可写"), "{}", message);
+    }
+
+    #[test]
+    fn validate_watch_root_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = clean_config(dir.path());
+
+        config.watch.roots.clear();
+        assert_eq!(single_problem(&config).0, ProblemLevel::Error);
+
+        config.watch.roots = vec![RootConfig::new(dir.path().join("missing"))];
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Warning && message.contains("不存在"), "{}", message);
+
+        fs::write(dir.path().join("file"), "").unwrap();
+        config.watch.roots = vec![RootConfig::new(dir.path().join("file"))];
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Error && message.contains("不是目录"), "{}", message);
+
+        let mut root = RootConfig::new(dir.path().join("docs"));
+        root.max_file_size = Some(0);
+        config.watch.roots = vec![root];
+        assert_eq!(single_problem(&config).0, ProblemLevel::Error);
+    }
+
+    #[test]
+    fn validate_detects_overlapping_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = clean_config(dir.path());
+        let docs = dir.path().join("docs");
+
+        // 不同写法的同一个目录
+        config.watch.roots = vec![RootConfig::new(&docs), RootConfig::new(docs.join("sub").join(".."))];
+        fs::create_dir(docs.join("sub")).unwrap();
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Error && message.contains("同一个目录"), "{}", message);
+
+        // 嵌套但什么都不覆盖的目录只是重复监控；有覆盖时是正常用法
+        config.watch.roots = vec![RootConfig::new(&docs), RootConfig::new(docs.join("sub"))];
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Warning && message.contains("可以去掉"), "{}", message);
+        config.watch.roots[1].ai_tags = Some(false);
+        assert_eq!(config.validate(), []);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn validate_detects_roots_linked_to_the_same_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = clean_config(dir.path());
+        std::os::unix::fs::symlink(dir.path().join("docs"), dir.path().join("link")).unwrap();
+
+        config.watch.roots.push(RootConfig::new(dir.path().join("link")));
+        assert_eq!(single_problem(&config).0, ProblemLevel::Error);
+    }
+
+    #[test]
+    fn validate_size_and_extension_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = clean_config(dir.path());
+
+        config.watch.max_file_size = 0;
+        assert_eq!(single_problem(&config).0, ProblemLevel::Error);
+        config.watch.max_file_size = MAX_FILE_SIZE;
+
+        config.watch.extensions.clear();
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Warning && message.contains("为空"), "{}", message);
+
+        config.watch.extensions = vec![".md".to_string()];
+        let (level, message) = single_problem(&config);
+        assert!(level == ProblemLevel::Warning && message.contains("不需要带点"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_keys_are_validation_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let clean = clean_config(dir.path());
+        let path = dir.path().join("ai_search.toml");
+        let content = clean.to_toml_string().unwrap().replace("[watch]\n", "[watch]\nexclude = [\"tmp\"]\n") + "\n[serach]\nlimit = 3\n";
+        fs::write(&path, content).unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.unknown_keys, ["serach", "watch.exclude"]);
+        let messages: Vec<_> = config.validate().into_iter().map(|problem| (problem.level, problem.message)).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages.iter().all(|(level, _)| *level == ProblemLevel::Warning));
+        assert!(messages[1].1.contains("watch.exclude"));
+        // 只是警告，不影响启动
+        assert!(config.validated().is_ok());
+    }
+
+    #[test]
+    fn validated_refuses_configs_with_errors() {
+        let mut config = Config::default();
+        config.watch.roots.clear();
+        config.watch.max_file_size = 0;
+        let error = config.validated().unwrap_err().to_string();
+        assert!(error.contains("watch.roots") && error.contains("watch.max_file_size"), "{}", error);
+    }
+
+    #[test]
+    fn toml_dump_round_trips() {
+        let mut config = Config::default();
+        config.index.heap_size = 30_000_000;
+        config.ai.enabled = false;
+        config.ai.model_path = Some(PathBuf::from("/models"));
+        config.scan.parallelism = 2;
+        let mut code = RootConfig::new("./code");
+        code.extensions = Some(vec!["rs".to_string()]);
+        code.exclude = vec!["/target/".to_string()];
+        code.ai_tags = Some(false);
+        config.watch.roots.push(code);
+
+        let dumped = config.to_toml_string().unwrap();
+        assert_eq!(toml::from_str::<Config>(&dumped).unwrap(), config, "{}", dumped);
+        // 导出的默认配置也能原样读回来
+        let defaults = Config::default().to_toml_string().unwrap();
+        assert_eq!(toml::from_str::<Config>(&defaults).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn load_from_reads_a_sample_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_search.toml");
+        fs::write(
+            &path,
+            r#"
+            [index]
+            storage_path = "/var/lib/ai_search"
+            heap_size = 30000000
+
+            [ai]
+            enabled = false
+            model_id = "Xenova/bge-small-zh-v1.5"
+
+            [watch]
+            roots = ["./notes", "./papers"]
+            extensions = ["md", "pdf"]
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.index.storage_path, PathBuf::from("/var/lib/ai_search"));
+        assert_eq!(config.index.heap_size, 30_000_000);
+        assert!(!config.ai.enabled);
+        assert_eq!(config.ai.model_id.as_deref(), Some("Xenova/bge-small-zh-v1.5"));
+        assert_eq!(config.watch.root_paths(), [PathBuf::from("./notes"), PathBuf::from("./papers")]);
+        assert_eq!(config.watch.extensions, ["md", "pdf"]);
+        assert!(config.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn omitted_sections_and_fields_use_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_search.toml");
+        fs::write(&path, "[ai]\ntags_per_doc = 5\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        let mut expected = Config::default();
+        expected.ai.tags_per_doc = 5;
+        assert_eq!(config, expected);
+
+        fs::write(&path, "").unwrap();
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+        // 文件不存在时 load_or_default 用默认配置，load_from 报错
+        assert_eq!(Config::load_or_default(&dir.path().join("missing.toml")).unwrap(), Config::default());
+        assert!(Config::load_from(&dir.path().join("missing.toml")).is_err());
+    }
+
+    #[test]
+    fn malformed_file_error_names_the_file_and_the_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.toml");
+
+        fs::write(&path, "[index]\nheap_size = \"50MB\"\n").unwrap();
+        let error = format!("{:#}", Config::load_from(&path).unwrap_err());
+        assert!(error.contains("broken.toml") && error.contains("格式错误"), "{}", error);
+        assert!(error.contains("heap_size") && error.contains("invalid type"), "{}", error);
+
+        fs::write(&path, "[watch\nroots = [\"./docs\"]\n").unwrap();
+        let error = format!("{:#}", Config::load_from(&path).unwrap_err());
+        assert!(error.contains("broken.toml") && error.contains("line 1"), "{}", error);
+    }
+
+    #[test]
+    fn out_of_range_knobs_are_clamped_with_warnings() {
+        let mut config = Config::default();
+        config.index.heap_size = 1_000_000;
+        config.index.commit_every_docs = 0;
+        config.ai.tags_per_doc = 100;
+        config.scan.parallelism = 2;
+        config.scan.inference_parallelism = 8;
+        config.watch.debounce_ms = 60_000;
+
+        let warnings = config.clamp_to_safe_range();
+        assert_eq!(warnings.len(), 5, "{:?}", warnings);
+        assert!(warnings[0].starts_with("index.heap_size = 1000000 超出范围"), "{}", warnings[0]);
+        assert_eq!(config.index.heap_size, MIN_WRITER_HEAP_SIZE);
+        assert_eq!(config.index.commit_every_docs, 1);
+        assert_eq!(config.ai.tags_per_doc, 20);
+        // 推理线程数不超过扫描线程数
+        assert_eq!((config.scan.parallelism, config.scan.inference_parallelism), (2, 2));
+        assert_eq!(config.watch.debounce_ms, 10_000);
+
+        // 范围内的值不动，也没有警告
+        assert!(config.clamp_to_safe_range().is_empty());
+    }
+
+    #[test]
+    fn load_from_clamps_knobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_search.toml");
+        fs::write(&path, "[scan]\nparallelism = 0\n\n[index]\ncommit_every_docs = 1\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.scan.parallelism, 1);
+        assert_eq!(config.index.commit_every_docs, 1);
+    }
+}
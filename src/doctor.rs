@@ -0,0 +1,420 @@
+// doctor.rs
+// 诊断运行环境：逐项检查配置文件、存储目录、索引、写锁、模型文件和监控目录
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fastembed::TextEmbedding;
+use tantivy::directory::MmapDirectory;
+use tantivy::directory::error::LockError;
+use tantivy::{Index, IndexWriter, TantivyError};
+
+use crate::ai::{BertModel, Tagger};
+use crate::cli::Overrides;
+use crate::config::{self, AiConfig, Config, ProblemLevel};
+use crate::indexer::build_schema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn label(self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    pub hint: Option<String>, // 出问题时给用户的修复建议
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Pass, message: message.into(), hint: None }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Warn, message: message.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Fail, message: message.into(), hint: Some(hint.into()) }
+    }
+}
+
+// 按顺序执行全部检查；deep 为 true 时会真正加载模型并跑一次推理
+// config_path 是 --config 显式指定的路径，None 时检查默认路径
+pub fn run_all(config_path: Option<&Path>, overrides: &Overrides, deep: bool) -> Vec<CheckResult> {
+    // 配置文件有问题时用默认配置继续检查其余项目
+    let (config_check, mut config) = check_config(config_path);
+    overrides.apply(&mut config);
+    let storage_path = &config.index.storage_path;
+
+    vec![
+        config_check,
+        check_storage_writable(storage_path),
+        check_index_schema(storage_path),
+        check_writer_lock(storage_path),
+        check_model_files(&config.ai, deep),
+        check_watch_roots(&config.watch.root_paths()),
+    ]
+}
+
+pub fn check_config(config_path: Option<&Path>) -> (CheckResult, Config) {
+    const NAME: &str = "配置文件";
+
+    let path = config_path.unwrap_or(Path::new(config::CONFIG_PATH));
+    if config_path.is_none() && !path.exists() {
+        return (CheckResult::pass(NAME, format!("{:?} 不存在，使用默认配置", path)), Config::default());
+    }
+
+    match Config::load_from(path) {
+        Ok(config) => {
+            let problems = config.validate();
+            let messages = problems.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("; ");
+            let result = if problems.iter().any(|p| p.level == ProblemLevel::Error) {
+                CheckResult::fail(NAME, messages, "按提示修改配置文件，修好之前程序会拒绝启动")
+            } else if !problems.is_empty() {
+                CheckResult::warn(NAME, messages, "可以运行，但建议按提示调整配置")
+            } else {
+                CheckResult::pass(NAME, format!("{:?} 读取成功", path))
+            };
+            (result, config)
+        }
+        Err(e) => (
+            CheckResult::fail(NAME, format!("{:#}", e), "按错误提示修改配置文件；其余检查暂时使用默认配置"),
+            Config::default(),
+        ),
+    }
+}
+
+pub fn check_storage_writable(storage_path: &Path) -> CheckResult {
+    const NAME: &str = "存储目录";
+
+    if !storage_path.exists() {
+        return CheckResult::warn(NAME, format!("{:?} 不存在", storage_path), "启动时会自动创建，无需处理");
+    }
+    if !storage_path.is_dir() {
+        return CheckResult::fail(NAME, format!("{:?} 不是目录", storage_path), "删除同名文件后重新启动");
+    }
+
+    // 写一个探测文件再删掉，比检查权限位更可靠
+    let probe = storage_path.join(".doctor_probe");
+    match fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe)) {
+        Ok(_) => CheckResult::pass(NAME, format!("{:?} 可写", storage_path)),
+        Err(e) => CheckResult::fail(NAME, format!("{:?} 不可写: {}", storage_path, e), "检查目录权限或磁盘空间"),
+    }
+}
+
+pub fn check_index_schema(storage_path: &Path) -> CheckResult {
+    const NAME: &str = "索引结构";
+
+    let index = match open_existing_index(storage_path) {
+        Ok(Some(index)) => index,
+        Ok(None) => return CheckResult::warn(NAME, "尚未建立索引", "首次启动时会自动扫描并建立索引"),
+        Err(e) => return CheckResult::fail(NAME, format!("索引无法打开: {}", e), format!("删除 {:?} 目录后重启，让程序重新构建索引", storage_path)),
+    };
+
+    let expected = build_schema();
+    let actual = index.schema();
+    if actual == expected {
+        return CheckResult::pass(NAME, "索引可以打开，字段结构与当前版本一致");
+    }
+
+    // 列出对不上的字段，方便判断是不是旧版本留下的索引
+    let mut mismatched: Vec<String> = Vec::new();
+    for (_field, entry) in expected.fields() {
+        match actual.get_field(entry.name()) {
+            Ok(field) if actual.get_field_entry(field) == entry => {}
+            Ok(_) => mismatched.push(format!("{}(类型不同)", entry.name())),
+            Err(_) => mismatched.push(format!("{}(缺失)", entry.name())),
+        }
+    }
+    for (_field, entry) in actual.fields() {
+        if expected.get_field(entry.name()).is_err() {
+            mismatched.push(format!("{}(多余)", entry.name()));
+        }
+    }
+    CheckResult::fail(
+        NAME,
+        format!("索引结构与当前版本不一致: {}", mismatched.join(", ")),
+        format!("删除 {:?} 目录后重启，让程序重新构建索引", storage_path),
+    )
+}
+
+pub fn check_writer_lock(storage_path: &Path) -> CheckResult {
+    const NAME: &str = "写入锁";
+
+    let index = match open_existing_index(storage_path) {
+        Ok(Some(index)) => index,
+        Ok(None) => return CheckResult::pass(NAME, "尚未建立索引，无需检查"),
+        Err(e) => return CheckResult::fail(NAME, format!("索引无法打开: {}", e), "先解决上面的索引问题"),
+    };
+
+    // tantivy 的写锁是文件锁，持有进程退出后会自动释放，
+    // 所以拿不到锁就说明确实有一个活着的进程在写
+    // 只是探测锁，用最小的内存预算就够了
+    let writer: tantivy::Result<IndexWriter> = index.writer(config::MIN_WRITER_HEAP_SIZE);
+    match writer {
+        Ok(_writer) => CheckResult::pass(NAME, "写入锁空闲"),
+        Err(TantivyError::LockFailure(LockError::LockBusy, _)) => CheckResult::warn(
+            NAME,
+            "写入锁被另一个正在运行的进程持有",
+            "如果不是有意同时运行多个 ai_search，请先关闭另一个实例",
+        ),
+        Err(e) => CheckResult::fail(NAME, format!("无法申请写入锁: {}", e), "检查存储目录权限"),
+    }
+}
+
+pub fn check_model_files(ai: &AiConfig, deep: bool) -> CheckResult {
+    const NAME: &str = "AI 模型";
+
+    if !ai.enabled {
+        return CheckResult::pass(NAME, "AI 已禁用，不需要模型文件");
+    }
+
+    let model_info = match ai.model().and_then(|model| TextEmbedding::get_model_info(&model).cloned()) {
+        Ok(info) => info,
+        Err(e) => return CheckResult::fail(NAME, format!("未知模型: {}", e), "检查 ai.model_id (或 --model-id) 是否拼写正确"),
+    };
+    let (model_dir, reload_hint) = match &ai.model_path {
+        // 指定了本地模型目录时不会自动下载，缺文件就启动不了
+        Some(dir) => {
+            let files = ai.local_model_files().unwrap_or_default();
+            let missing: Vec<&str> = files.iter().map(String::as_str).filter(|file| !is_non_empty_file(&dir.join(file))).collect();
+            if !missing.is_empty() {
+                return CheckResult::fail(
+                    NAME,
+                    format!("本地模型目录 {:?} 缺少文件: {}", dir, missing.join(", ")),
+                    format!("把 {} 的仓库完整下载到这个目录，或去掉 ai.model_path (--model-path) 改用自动下载", model_info.model_code),
+                );
+            }
+            (dir.clone(), format!("检查 {:?} 里的模型文件是否完整", dir))
+        }
+        None => {
+            let cache_dir = ai.cache_dir();
+
+            // hf-hub 的缓存布局: <cache>/models--<org>--<name>/snapshots/<revision>/<file>
+            let repo_dir = cache_dir.join(format!("models--{}", model_info.model_code.replace('/', "--")));
+            let required = [model_info.model_file.as_str(), "tokenizer.json", "config.json"];
+            let snapshot = fs::read_dir(repo_dir.join("snapshots"))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .find(|dir| required.iter().all(|file| is_non_empty_file(&dir.join(file))));
+
+            let Some(snapshot) = snapshot else {
+                return CheckResult::warn(
+                    NAME,
+                    format!("{:?} 下没有找到 {} 的模型文件", cache_dir, model_info.model_code),
+                    "首次启动会自动下载 (约 200MB)，请确认网络可以访问 Hugging Face",
+                );
+            };
+            (snapshot, format!("删除 {:?} 后重启，让程序重新下载模型", repo_dir))
+        }
+    };
+
+    if !deep {
+        return CheckResult::pass(NAME, format!("模型文件齐全: {:?} (加 --deep 可实际加载验证)", model_dir));
+    }
+
+    let loaded = BertModel::load(ai).and_then(|bert| bert.extract_keywords("人工智能是计算机科学的一个重要分支", 1));
+    match loaded {
+        Ok(_) => CheckResult::pass(NAME, format!("模型加载并推理成功: {:?}", model_dir)),
+        Err(e) => CheckResult::fail(NAME, format!("模型文件存在但无法加载: {}", e), reload_hint),
+    }
+}
+
+pub fn check_watch_roots(roots: &[PathBuf]) -> CheckResult {
+    const NAME: &str = "监控目录";
+
+    if roots.is_empty() {
+        return CheckResult::fail(NAME, "没有配置任何监控目录", "在配置文件 [watch] roots 中至少写一个目录");
+    }
+
+    let not_dirs: Vec<&PathBuf> = roots.iter().filter(|r| r.exists() && !r.is_dir()).collect();
+    if !not_dirs.is_empty() {
+        return CheckResult::fail(NAME, format!("{:?} 不是目录", not_dirs), "删除同名文件或修改 [watch] roots");
+    }
+
+    let missing: Vec<&PathBuf> = roots.iter().filter(|r| !r.exists()).collect();
+    if !missing.is_empty() {
+        return CheckResult::warn(NAME, format!("{:?} 不存在", missing), "启动时会自动创建，放入文档即可索引");
+    }
+
+    CheckResult::pass(NAME, format!("{:?} 均存在", roots))
+}
+
+pub fn has_failures(results: &[CheckResult]) -> bool {
+    results.iter().any(|r| r.status == CheckStatus::Fail)
+}
+
+pub fn render_report(results: &[CheckResult]) -> String {
+    let mut lines = Vec::new();
+    for result in results {
+        lines.push(format!("[{}] {}: {}", result.status.label(), result.name, result.message));
+        if let Some(hint) = &result.hint {
+            lines.push(format!("       建议: {}", hint));
+        }
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    lines.push(format!(
+        "共 {} 项检查: {} 通过, {} 警告, {} 失败",
+        results.len(),
+        count(CheckStatus::Pass),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail)
+    ));
+    lines.join("\n")
+}
+
+// 只打开已存在的索引，不像 init_persistent_index 那样顺手创建
+fn open_existing_index(storage_path: &Path) -> tantivy::Result<Option<Index>> {
+    if !storage_path.is_dir() {
+        return Ok(None);
+    }
+    let directory = MmapDirectory::open(storage_path)?;
+    if !Index::exists(&directory)? {
+        return Ok(None);
+    }
+    Index::open(directory).map(Some)
+}
+
+fn is_non_empty_file(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.is_file() && m.len() > 0).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::init_persistent_index;
+    use tantivy::schema::{STORED, STRING, Schema, TEXT};
+
+    #[test]
+    fn index_schema_passes_for_current_version_and_lists_mismatches() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = dir.path().join("storage");
+        assert_eq!(check_index_schema(&storage).status, CheckStatus::Warn);
+
+        init_persistent_index(&storage).unwrap();
+        assert_eq!(check_index_schema(&storage).status, CheckStatus::Pass);
+
+        // 旧版本的索引：没有 tags 和 timestamp，path 还是分词字段，多一个 content
+        let old = dir.path().join("old");
+        fs::create_dir(&old).unwrap();
+        let mut builder = Schema::builder();
+        builder.add_text_field("title", TEXT | STORED);
+        builder.add_text_field("body", TEXT | STORED);
+        builder.add_text_field("path", TEXT | STORED);
+        builder.add_text_field("content", STRING);
+        Index::create_in_dir(&old, builder.build()).unwrap();
+
+        let result = check_index_schema(&old);
+        assert_eq!(result.status, CheckStatus::Fail);
+        for field in ["title(类型不同)", "path(类型不同)", "tags(缺失)", "timestamp(缺失)", "content(多余)"] {
+            assert!(result.message.contains(field), "{} 不在 {}", field, result.message);
+        }
+        assert!(result.hint.unwrap().contains("old"));
+    }
+
+    #[test]
+    fn writer_lock_warns_while_another_writer_holds_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = dir.path().join("storage");
+        assert_eq!(check_writer_lock(&storage).status, CheckStatus::Pass);
+
+        let (index, _schema) = init_persistent_index(&storage).unwrap();
+        assert_eq!(check_writer_lock(&storage).status, CheckStatus::Pass);
+
+        let writer: IndexWriter = index.writer(config::MIN_WRITER_HEAP_SIZE).unwrap();
+        let result = check_writer_lock(&storage);
+        assert_eq!(result.status, CheckStatus::Warn, "{}", result.message);
+        drop(writer);
+        assert_eq!(check_writer_lock(&storage).status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn config_check_reports_malformed_toml_and_falls_back_to_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_search.toml");
+        fs::write(&path, "[watch\nroots = 1\n").unwrap();
+
+        let (result, config) = check_config(Some(&path));
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert!(result.message.contains("格式错误"), "{}", result.message);
+        assert_eq!(config, Config::default());
+
+        // 显式指定的文件不存在也是失败，默认路径不存在则直接用默认配置
+        let (result, _) = check_config(Some(&dir.path().join("missing.toml")));
+        assert_eq!(result.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn config_check_surfaces_validation_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_search.toml");
+        fs::write(&path, format!("[watch]\nroots = [{:?}]\nextension = [\"md\"]\n", dir.path())).unwrap();
+
+        let (result, _) = check_config(Some(&path));
+        assert_eq!(result.status, CheckStatus::Warn);
+        assert!(result.message.contains("watch.extension"), "{}", result.message);
+    }
+
+    #[test]
+    fn watch_roots_check_missing_and_non_directory_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "").unwrap();
+        let missing = dir.path().join("missing");
+
+        assert_eq!(check_watch_roots(&[dir.path().to_path_buf()]).status, CheckStatus::Pass);
+        assert_eq!(check_watch_roots(&[]).status, CheckStatus::Fail);
+        assert_eq!(check_watch_roots(&[dir.path().to_path_buf(), missing.clone()]).status, CheckStatus::Warn);
+        // 不是目录比不存在更严重
+        assert_eq!(check_watch_roots(&[missing, file]).status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn local_model_dir_must_have_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let ai = AiConfig { model_path: Some(dir.path().to_path_buf()), ..AiConfig::default() };
+        fs::write(dir.path().join("tokenizer.json"), "{}").unwrap();
+
+        let result = check_model_files(&ai, false);
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert!(result.message.contains("onnx/model.onnx") && !result.message.contains("tokenizer.json,"), "{}", result.message);
+
+        for file in ai.local_model_files().unwrap() {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "{}").unwrap();
+        }
+        assert_eq!(check_model_files(&ai, false).status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn report_counts_each_status() {
+        let results = [
+            CheckResult::pass("配置文件", "ok"),
+            CheckResult::warn("监控目录", "不存在", "会自动创建"),
+            CheckResult::fail("写入锁", "失败", "检查权限"),
+        ];
+        let report = render_report(&results);
+        assert!(report.contains("[WARN] 监控目录: 不存在\n       建议: 会自动创建"), "{}", report);
+        assert!(report.ends_with("共 3 项检查: 1 通过, 1 警告, 1 失败"), "{}", report);
+        assert!(has_failures(&results));
+        assert!(!has_failures(&results[..2]));
+    }
+}
@@ -0,0 +1,161 @@
+// events.rs
+// 索引过程中的事件 (文件已索引、已删除、扫描进度、AI 状态)，供 HTTP 的 /events 等实时展示。
+// 事件总线由 AppState 持有，传给扫描、监控线程和接口层的索引操作，不是进程全局的。
+// 发布方永远不会被订阅方拖慢：每个订阅者有一个固定长度的队列，满了就丢掉最旧的事件，
+// 订阅者下次取事件时会先收到一条 Lagged，说明中间丢了多少条。
+// 同步代码用 recv_timeout 等事件；HTTP 服务 (--features server) 用 recv().await，不占用阻塞线程
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::Duration;
+
+use serde::Serialize;
+
+pub const EVENT_QUEUE_CAPACITY: usize = 256; // 每个订阅者最多缓存的事件数
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineEvent {
+    ScanStarted { roots: Vec<PathBuf>, pending: usize },
+    ScanProgress { processed: usize, pending: usize },
+    ScanFinished { indexed: usize, interrupted: bool },
+    FileIndexed { path: String, title: String, tags: Vec<String> },
+    FileFailed { path: String, error: String },
+    FileDeleted { path: String },
+    AiStatus { enabled: bool, detail: String },
+    Lagged { skipped: usize }, // 订阅者处理太慢，中间有事件被丢弃
+}
+
+impl EngineEvent {
+    // 事件类型名，和 JSON 里的 "type" 一致 (SSE 的 event: 字段用它)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EngineEvent::ScanStarted { .. } => "scan_started",
+            EngineEvent::ScanProgress { .. } => "scan_progress",
+            EngineEvent::ScanFinished { .. } => "scan_finished",
+            EngineEvent::FileIndexed { .. } => "file_indexed",
+            EngineEvent::FileFailed { .. } => "file_failed",
+            EngineEvent::FileDeleted { .. } => "file_deleted",
+            EngineEvent::AiStatus { .. } => "ai_status",
+            EngineEvent::Lagged { .. } => "lagged",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Weak<SubscriberQueue>>>,
+}
+
+#[derive(Default)]
+struct SubscriberQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    #[cfg(feature = "server")]
+    notify: tokio::sync::Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<EngineEvent>,
+    skipped: usize, // 自上次取事件以来丢掉的条数
+}
+
+// 一个订阅；drop 之后事件总线会自动把它移除
+pub struct Subscription {
+    queue: Arc<SubscriberQueue>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Subscription {
+        let queue = Arc::new(SubscriberQueue::default());
+        self.subscribers.lock().unwrap().push(Arc::downgrade(&queue));
+        Subscription { queue }
+    }
+
+    pub fn publish(&self, event: EngineEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // 顺便清理已经断开的订阅
+        subscribers.retain(|subscriber| subscriber.strong_count() > 0);
+        for queue in subscribers.iter().filter_map(Weak::upgrade) {
+            let mut state = queue.state.lock().unwrap();
+            if state.events.len() >= EVENT_QUEUE_CAPACITY {
+                state.events.pop_front();
+                state.skipped += 1;
+            }
+            state.events.push_back(event.clone());
+            queue.ready.notify_one();
+            // 没有人在等时 notify_one 会留下一个许可，下一次 notified() 立即返回，不会漏掉唤醒
+            #[cfg(feature = "server")]
+            queue.notify.notify_one();
+        }
+    }
+}
+
+impl Subscription {
+    // 等待下一条事件，超时返回 None；有事件被丢弃时先返回一条 Lagged
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<EngineEvent> {
+        let state = self.queue.state.lock().unwrap();
+        let (mut state, _) = self
+            .queue
+            .ready
+            .wait_timeout_while(state, timeout, |state| state.events.is_empty() && state.skipped == 0)
+            .unwrap();
+
+        take_next(&mut state)
+    }
+
+    // 异步等待下一条事件；有事件被丢弃时先返回一条 Lagged
+    #[cfg(feature = "server")]
+    pub async fn recv(&self) -> EngineEvent {
+        loop {
+            if let Some(event) = take_next(&mut self.queue.state.lock().unwrap()) {
+                return event;
+            }
+            self.queue.notify.notified().await;
+        }
+    }
+}
+
+fn take_next(state: &mut QueueState) -> Option<EngineEvent> {
+    if state.skipped > 0 {
+        let skipped = std::mem::take(&mut state.skipped);
+        return Some(EngineEvent::Lagged { skipped });
+    }
+    state.events.pop_front()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deleted(i: usize) -> EngineEvent {
+        EngineEvent::FileDeleted { path: format!("{}.txt", i) }
+    }
+
+    #[test]
+    fn slow_subscriber_gets_lagged_then_newest_events() {
+        let bus = EventBus::default();
+        let subscription = bus.subscribe();
+        for i in 0..EVENT_QUEUE_CAPACITY + 3 {
+            bus.publish(deleted(i));
+        }
+        let timeout = Duration::from_millis(10);
+        assert_eq!(subscription.recv_timeout(timeout), Some(EngineEvent::Lagged { skipped: 3 }));
+        assert_eq!(subscription.recv_timeout(timeout), Some(deleted(3)));
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn async_recv_wakes_up_on_publish() {
+        let bus = Arc::new(EventBus::default());
+        let subscription = bus.subscribe();
+        let publisher = bus.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            publisher.publish(deleted(1));
+        });
+        let event = tokio::time::timeout(Duration::from_secs(5), subscription.recv()).await.unwrap();
+        assert_eq!(event, deleted(1));
+    }
+}
@@ -1,24 +1,25 @@
 use std::fs;
 use std::path::Path;
-use std::time::Duration;
 use anyhow::{Result, Context};
 use pdf_extract;
 
 use crate::models::FileDoc;
 use crate::config::{PREVIEW_MAX_LENGTH, SENTENCE_SEARCH_START};
 
-pub fn extract_text(path: &Path) -> Result<FileDoc> {
-    // 简单的防抖动：如果是刚创建的文件，可能还在写入中，稍微等一下
-    // 实际生产中通常用 Debouncer，这里简化处理
-    std::thread::sleep(Duration::from_millis(100));
+// extract_text 能处理的扩展名，和下面的 match 保持一致；配置里写了其他扩展名时校验会给出警告
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["txt", "md", "rs", "pdf"];
 
+// 刚创建的文件可能还在写入，等待由监控线程按 watch.debounce_ms 负责，这里直接读取
+pub fn extract_text(path: &Path) -> Result<FileDoc> {
+    // 和过滤规则一样不区分大小写，"A.TXT" 也能提取
     let extension = path.extension()
         .and_then(|ext| ext.to_str())
-        .unwrap_or("");
+        .unwrap_or("")
+        .to_lowercase();
 
-    println!("正在解析文件: {:?}", path);
+    eprintln!("正在解析文件: {:?}", path);
 
-    let content = match extension {
+    let content = match extension.as_str() {
         "txt" | "md" | "rs" => fs::read_to_string(path)?,
         "pdf" => pdf_extract::extract_text(path).with_context(|| "无法解析 PDF")?,
         _ => return Err(anyhow::anyhow!("跳过不支持的文件格式")),
@@ -47,14 +48,13 @@ pub fn format_content_preview(content: &str) -> String {
 
         // 从第PREVIEW_MAX_LENGTH个字符开始向前查找最近的句子结束符
         for i in (SENTENCE_SEARCH_START..=PREVIEW_MAX_LENGTH).rev() {  // 从PREVIEW_MAX_LENGTH向前到SENTENCE_SEARCH_START查找，给出更大的搜索范围
-            if i < cleaned_content.len() {
-                if let Some(ch) = cleaned_content.chars().nth(i) {
-                    if sentence_endings.contains(&ch) {
-                        end_pos = i + 1;  // 包含句子结束符
-                        found_sentence_end = true;
-                        break;
-                    }
-                }
+            if i < cleaned_content.len()
+                && let Some(ch) = cleaned_content.chars().nth(i)
+                && sentence_endings.contains(&ch)
+            {
+                end_pos = i + 1;  // 包含句子结束符
+                found_sentence_end = true;
+                break;
             }
         }
 
@@ -63,13 +63,12 @@ pub fn format_content_preview(content: &str) -> String {
             end_pos = PREVIEW_MAX_LENGTH;
             // 尝试在单词边界处截断（查找空格或标点）
             for i in ((PREVIEW_MAX_LENGTH - SENTENCE_SEARCH_START)..=PREVIEW_MAX_LENGTH).rev() {
-                if i < cleaned_content.len() {
-                    if let Some(ch) = cleaned_content.chars().nth(i) {
-                        if ch.is_whitespace() || ch == '，' || ch == '。' || ch == '；' {
-                            end_pos = i;
-                            break;
-                        }
-                    }
+                if i < cleaned_content.len()
+                    && let Some(ch) = cleaned_content.chars().nth(i)
+                    && (ch.is_whitespace() || ch == '，' || ch == '。' || ch == '；')
+                {
+                    end_pos = i;
+                    break;
                 }
             }
         }
@@ -0,0 +1,356 @@
+// grpc.rs
+// gRPC 接口 (编译时需要 --features grpc)：给内部服务调用，方法和 HTTP 接口一一对应。
+// 消息定义在 proto/ai_search.proto，这里负责 proto 消息和 api 里的类型互相转换；
+// 错误按 ApiErrorKind 映射成 gRPC 状态码，和 HTTP 的状态码含义一致
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use tonic::metadata::MetadataMap;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Code, Request, Response, Status};
+
+use crate::api::{
+    ApiErrorKind, BatchIndexRequest, BatchIndexResponse, DEFAULT_SUGGEST_LIMIT, DeleteRequest, DeleteResponse, IndexRequest,
+    IndexResult, SearchRequest, SearchResponse, StatsResponse, SuggestRequest, Suggestion, token_matches,
+};
+use crate::app::AppState;
+use crate::indexer;
+use crate::search::{self, SearchHit, SearchOptions};
+
+// tonic-build 从 proto/ai_search.proto 生成的代码
+pub mod proto {
+    tonic::include_proto!("ai_search");
+}
+
+use proto::ai_search_server::{AiSearch, AiSearchServer};
+
+// ---- proto -> api ----
+
+impl From<proto::SearchRequest> for SearchRequest {
+    fn from(request: proto::SearchRequest) -> Self {
+        let defaults = SearchOptions::default();
+        let options = SearchOptions {
+            offset: request.offset.map_or(defaults.offset, |offset| offset as usize),
+            limit: request.limit.map_or(defaults.limit, |limit| limit as usize),
+            file_type: request.file_type.filter(|file_type| !file_type.is_empty()),
+        };
+        Self { query: request.query, options }
+    }
+}
+
+impl From<proto::IndexRequest> for IndexRequest {
+    fn from(request: proto::IndexRequest) -> Self {
+        Self { path: PathBuf::from(request.path) }
+    }
+}
+
+impl From<proto::BatchIndexRequest> for BatchIndexRequest {
+    fn from(request: proto::BatchIndexRequest) -> Self {
+        Self { paths: request.paths.into_iter().map(PathBuf::from).collect() }
+    }
+}
+
+impl From<proto::DeleteRequest> for DeleteRequest {
+    fn from(request: proto::DeleteRequest) -> Self {
+        Self { path: request.path }
+    }
+}
+
+impl From<proto::SuggestRequest> for SuggestRequest {
+    fn from(request: proto::SuggestRequest) -> Self {
+        Self { prefix: request.prefix, limit: request.limit.map_or(DEFAULT_SUGGEST_LIMIT, |limit| limit as usize) }
+    }
+}
+
+// ---- api -> proto ----
+
+impl From<SearchHit> for proto::SearchHit {
+    fn from(hit: SearchHit) -> Self {
+        Self {
+            title: hit.title,
+            path: hit.path,
+            tags: hit.tags,
+            score: hit.score,
+            snippet: hit.snippet,
+            highlights: hit
+                .highlights
+                .into_iter()
+                .map(|range| proto::Highlight { start: range.start as u32, end: range.end as u32 })
+                .collect(),
+        }
+    }
+}
+
+impl From<SearchResponse> for proto::SearchResponse {
+    fn from(page: SearchResponse) -> Self {
+        Self {
+            hits: page.hits.into_iter().map(Into::into).collect(),
+            total: page.total as u64,
+            offset: page.offset as u32,
+            limit: page.limit as u32,
+        }
+    }
+}
+
+impl From<IndexResult> for proto::IndexResult {
+    fn from(result: IndexResult) -> Self {
+        Self { path: result.path.to_string_lossy().into_owned(), title: result.title, error: result.error }
+    }
+}
+
+impl From<BatchIndexResponse> for proto::BatchIndexResponse {
+    fn from(response: BatchIndexResponse) -> Self {
+        Self {
+            indexed: response.indexed as u32,
+            failed: response.failed as u32,
+            results: response.results.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<DeleteResponse> for proto::DeleteResponse {
+    fn from(response: DeleteResponse) -> Self {
+        Self { deleted: response.deleted }
+    }
+}
+
+impl From<StatsResponse> for proto::StatsResponse {
+    fn from(stats: StatsResponse) -> Self {
+        Self {
+            num_docs: stats.num_docs,
+            num_segments: stats.num_segments as u32,
+            ai_enabled: stats.ai_enabled,
+            watch_roots: stats.watch_roots.iter().map(|root| root.to_string_lossy().into_owned()).collect(),
+        }
+    }
+}
+
+impl From<Suggestion> for proto::Suggestion {
+    fn from(suggestion: Suggestion) -> Self {
+        Self { tag: suggestion.tag, count: suggestion.count as u64 }
+    }
+}
+
+// ---- 错误 ----
+
+fn status(kind: ApiErrorKind, message: impl Into<String>) -> Status {
+    let code = match kind {
+        ApiErrorKind::InvalidRequest => Code::InvalidArgument,
+        ApiErrorKind::Unauthorized => Code::Unauthenticated,
+        ApiErrorKind::Forbidden => Code::PermissionDenied,
+        ApiErrorKind::NotFound => Code::NotFound,
+        ApiErrorKind::Unprocessable => Code::FailedPrecondition,
+        ApiErrorKind::Busy => Code::Unavailable,
+        ApiErrorKind::Internal => Code::Internal,
+    };
+    Status::new(code, message)
+}
+
+fn unauthorized() -> Status {
+    status(ApiErrorKind::Unauthorized, "缺少或错误的访问令牌 (authorization: Bearer <token>)")
+}
+
+fn internal_status(e: anyhow::Error) -> Status {
+    status(ApiErrorKind::classify(&e), format!("{:#}", e))
+}
+
+// ---- 服务 ----
+
+pub struct GrpcService {
+    app: Arc<AppState>,
+    token: Option<Arc<str>>, // 设置后，修改索引的方法需要带 authorization: Bearer <token> 元数据
+}
+
+impl GrpcService {
+    pub fn new(app: Arc<AppState>, token: Option<String>) -> Self {
+        Self { app, token: token.map(Arc::from) }
+    }
+
+    // 和 HTTP 一样，只有修改索引的方法检查令牌；没配置令牌时全部放行
+    fn is_authorized(&self, metadata: &MetadataMap) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let provided = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        token_matches(token, provided)
+    }
+}
+
+#[tonic::async_trait]
+impl AiSearch for GrpcService {
+    async fn search(&self, request: Request<proto::SearchRequest>) -> Result<Response<proto::SearchResponse>, Status> {
+        let request = SearchRequest::from(request.into_inner());
+        if request.query.trim().is_empty() {
+            return Err(status(ApiErrorKind::InvalidRequest, "query 不能为空"));
+        }
+        let app = self.app.clone();
+        let page = run_blocking(move || search::search_page(&app.index, &request.query, &request.options)).await?;
+        Ok(Response::new(page.into()))
+    }
+
+    async fn index_file(&self, request: Request<proto::IndexRequest>) -> Result<Response<proto::IndexResult>, Status> {
+        if !self.is_authorized(request.metadata()) {
+            return Err(unauthorized());
+        }
+        let request = IndexRequest::from(request.into_inner());
+        let config = self.app.config();
+        let path = indexer::resolve_external_path(&request.path, &config)
+            .map_err(|rejection| status(rejection.into(), format!("{:?}: {}", request.path, rejection)))?;
+
+        let app = self.app.clone();
+        let paths = vec![path.clone()];
+        let mut results = run_blocking(move || indexer::index_paths(&paths, &app.index, &app.schema, app.bert.as_deref(), &config, app.events())).await?;
+        match results.remove(0) {
+            Ok(title) => Ok(Response::new(IndexResult { path, title: Some(title), error: None }.into())),
+            Err(e) => Err(status(ApiErrorKind::Unprocessable, format!("处理文件失败 {:?}: {:#}", path, e))),
+        }
+    }
+
+    async fn batch_index(&self, request: Request<proto::BatchIndexRequest>) -> Result<Response<proto::BatchIndexResponse>, Status> {
+        if !self.is_authorized(request.metadata()) {
+            return Err(unauthorized());
+        }
+        let request = BatchIndexRequest::from(request.into_inner());
+
+        let app = self.app.clone();
+        let response = run_blocking(move || BatchIndexResponse::run(request, &app.index, &app.schema, app.bert.as_deref(), &app.config(), app.events())).await?;
+        Ok(Response::new(response.into()))
+    }
+
+    async fn delete(&self, request: Request<proto::DeleteRequest>) -> Result<Response<proto::DeleteResponse>, Status> {
+        if !self.is_authorized(request.metadata()) {
+            return Err(unauthorized());
+        }
+        let request = DeleteRequest::from(request.into_inner());
+
+        let app = self.app.clone();
+        let path = request.path.clone();
+        let deleted = run_blocking(move || indexer::delete_document(&app.index, &app.schema, &path, &app.config(), app.events())).await?;
+        if deleted {
+            Ok(Response::new(DeleteResponse { deleted }.into()))
+        } else {
+            Err(status(ApiErrorKind::NotFound, format!("索引中没有这个文件: {}", request.path)))
+        }
+    }
+
+    async fn stats(&self, _request: Request<proto::StatsRequest>) -> Result<Response<proto::StatsResponse>, Status> {
+        let app = self.app.clone();
+        let stats = run_blocking(move || StatsResponse::collect(&app.index, app.bert.is_some(), &app.config())).await?;
+        Ok(Response::new(stats.into()))
+    }
+
+    async fn suggest(&self, request: Request<proto::SuggestRequest>) -> Result<Response<proto::SuggestResponse>, Status> {
+        let request = SuggestRequest::from(request.into_inner());
+
+        let app = self.app.clone();
+        let tags = run_blocking(move || search::list_tags(&app.index, &request.prefix, request.limit)).await?;
+        let suggestions = tags.into_iter().map(|(tag, count)| Suggestion { tag, count }.into()).collect();
+        Ok(Response::new(proto::SuggestResponse { suggestions }))
+    }
+}
+
+// 在已经绑定好的端口上启动 gRPC 服务并阻塞，直到 app 的退出标志被置位 (Ctrl-C)。
+// 端口由调用方先绑定，这样可以先监听、再在后台开始扫描
+pub fn serve(app: Arc<AppState>, listener: TcpListener, token: Option<String>) -> Result<()> {
+    let addr = listener.local_addr()?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    runtime.block_on(async move {
+        let incoming = TcpIncoming::from_listener(tokio::net::TcpListener::from_std(listener)?, true, None)
+            .map_err(|e| anyhow!("gRPC 服务无法在 {} 上运行: {}", addr, e))?;
+        println!(" [服务] gRPC 接口已启动: {}", addr);
+
+        let shutdown_app = app.clone();
+        Server::builder()
+            .add_service(AiSearchServer::new(GrpcService::new(app, token)))
+            .serve_with_incoming_shutdown(incoming, async move {
+                while !shutdown_app.is_shutting_down() {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+            })
+            .await
+            .with_context(|| format!("gRPC 服务无法在 {} 上运行", addr))?;
+        Ok(())
+    })
+}
+
+async fn run_blocking<T, F>(f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| status(ApiErrorKind::Internal, format!("后台任务异常退出: {}", e)))?
+        .map_err(internal_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_request_fills_in_defaults() {
+        let request = SearchRequest::from(proto::SearchRequest { query: "rust".to_string(), ..Default::default() });
+        assert_eq!(request.query, "rust");
+        assert_eq!(request.options, SearchOptions::default());
+
+        let request = SearchRequest::from(proto::SearchRequest {
+            query: "rust".to_string(),
+            offset: Some(20),
+            limit: Some(50),
+            file_type: Some("pdf".to_string()),
+        });
+        assert_eq!(request.options, SearchOptions { offset: 20, limit: 50, file_type: Some("pdf".to_string()) });
+    }
+
+    #[test]
+    fn empty_file_type_means_no_filter() {
+        let request = SearchRequest::from(proto::SearchRequest { file_type: Some(String::new()), ..Default::default() });
+        assert_eq!(request.options.file_type, None);
+    }
+
+    #[test]
+    fn suggest_limit_defaults_like_http() {
+        assert_eq!(SuggestRequest::from(proto::SuggestRequest::default()).limit, DEFAULT_SUGGEST_LIMIT);
+        assert_eq!(SuggestRequest::from(proto::SuggestRequest { limit: Some(3), ..Default::default() }).limit, 3);
+    }
+
+    #[test]
+    fn search_page_converts_to_proto() {
+        let page = SearchResponse {
+            hits: vec![SearchHit {
+                title: "rust".to_string(),
+                path: "/docs/rust.txt".to_string(),
+                tags: vec!["编程".to_string()],
+                score: 1.5,
+                snippet: "Rust 所有权".to_string(),
+                highlights: vec![0..4, 5..14],
+            }],
+            total: 7,
+            offset: 5,
+            limit: 1,
+        };
+        let response = proto::SearchResponse::from(page);
+        assert_eq!((response.total, response.offset, response.limit), (7, 5, 1));
+        let hit = &response.hits[0];
+        assert_eq!((hit.title.as_str(), hit.path.as_str(), hit.score), ("rust", "/docs/rust.txt", 1.5));
+        assert_eq!(hit.tags, ["编程"]);
+        assert_eq!(hit.highlights, [proto::Highlight { start: 0, end: 4 }, proto::Highlight { start: 5, end: 14 }]);
+    }
+
+    #[test]
+    fn error_kinds_map_to_grpc_codes() {
+        assert_eq!(status(ApiErrorKind::InvalidRequest, "").code(), Code::InvalidArgument);
+        assert_eq!(status(ApiErrorKind::Forbidden, "").code(), Code::PermissionDenied);
+        assert_eq!(status(ApiErrorKind::Busy, "").code(), Code::Unavailable);
+        assert_eq!(internal_status(anyhow!("磁盘坏了")).code(), Code::Internal);
+    }
+}
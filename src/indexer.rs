@@ -3,22 +3,27 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::channel;
-use std::thread;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, SystemTime};
 use anyhow::Result;
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
 
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher, EventKind};
+use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher, EventKind};
 use tantivy::schema::*;
 use tantivy::{Index, doc, IndexWriter, Term};
 use tantivy_jieba::JiebaTokenizer;
+use walkdir::WalkDir;
 
-use crate::ai::BertModel;
+use crate::ai::Tagger;
+use crate::config::{Config, ConfigHandle};
+use crate::events::{EngineEvent, EventBus};
 use crate::extract::extract_text; // 使用 crate 内部引用
+use crate::search::get_document;
 
-// 初始化持久化索引
-pub fn init_persistent_index(index_path: &Path) -> Result<(Index, Schema)> {
+// 索引结构定义 (doctor 也用它来比对磁盘上的旧索引)
+pub fn build_schema() -> Schema {
     let mut schema_builder = Schema::builder();
 
     let text_options = TextOptions::default()
@@ -35,7 +40,12 @@ pub fn init_persistent_index(index_path: &Path) -> Result<(Index, Schema)> {
     schema_builder.add_text_field("tags", text_options.clone());
     schema_builder.add_u64_field("timestamp", FAST | STORED);
 
-    let schema = schema_builder.build();
+    schema_builder.build()
+}
+
+// 初始化持久化索引
+pub fn init_persistent_index(index_path: &Path) -> Result<(Index, Schema)> {
+    let schema = build_schema();
 
     if !index_path.exists() {
         fs::create_dir_all(index_path)?;
@@ -49,6 +59,14 @@ pub fn init_persistent_index(index_path: &Path) -> Result<(Index, Schema)> {
     Ok((index, schema))
 }
 
+// 不落盘的内存索引 (测试和一次性的临时索引用)，schema 和分词器与持久化索引相同
+pub fn init_ram_index() -> (Index, Schema) {
+    let schema = build_schema();
+    let index = Index::create_in_ram(schema.clone());
+    index.tokenizers().register("jieba", JiebaTokenizer {});
+    (index, schema)
+}
+
 // 检查文件是否需要索引
 // 返回 true 表示：数据库里没这个文件，或者文件变新了，需要重新搞
 fn should_index_file(path: &Path, index: &Index, schema: &Schema) -> bool {
@@ -100,13 +118,49 @@ fn should_index_file(path: &Path, index: &Index, schema: &Schema) -> bool {
     current_ts > stored_ts
 }
 
-// 处理单个文件 (改为 pub 供 watcher 使用)
-pub fn process_and_index(file_path: &Path, index: &Index, schema: &Schema, bert: &BertModel) -> Result<()> {
+// 提取好文本和标签、等待写入索引的文档
+struct PreparedDoc {
+    title: String,
+    path: String,
+    content: String,
+    tags: String,
+    timestamp: u64,
+}
+
+// 限制同时进行模型推理的线程数 (推理很吃内存，小机器上并发太多会被 OOM)
+struct InferenceLimit {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl InferenceLimit {
+    fn new(permits: usize) -> Self {
+        Self { available: Mutex::new(permits.max(1)), released: Condvar::new() }
+    }
+
+    fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= 1;
+        drop(available);
+
+        let result = f();
+
+        *self.available.lock().unwrap() += 1;
+        self.released.notify_one();
+        result
+    }
+}
+
+// 提取文本并生成标签 (耗时的部分，可以在多个线程里并行做)
+fn prepare_document(file_path: &Path, bert: Option<&dyn Tagger>, config: &Config, inference: &InferenceLimit) -> Result<PreparedDoc> {
     // 调用 extract 模块的功能
     let doc_data = extract_text(file_path)?;
 
     //获取文件当前时间戳
-    let file_timestamp = fs::metadata(file_path)
+    let timestamp = fs::metadata(file_path)
         .and_then(|m| m.modified())
         .unwrap_or(SystemTime::now())
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -114,131 +168,354 @@ pub fn process_and_index(file_path: &Path, index: &Index, schema: &Schema, bert:
         .as_secs();
 
     // --- AI 核心步骤：生成关键词 ---
-    println!("   [AI] 正在分析文档语义...");
-    let keywords = bert.extract_keywords(&doc_data.content, 3)?; // 提取 3 个关键词
-    let tags_str = keywords.join(" "); // 变成 "Rust 编程 教程" 这样的字符串存入
-    println!("   [AI] 生成标签: {:?}", keywords);
+    // 所属监控目录关闭了 ai_tags 时跳过 (比如文件很多的代码目录)
+    let bert = bert.filter(|_| config.rules_for(file_path).ai_tags);
+    let tags = match bert {
+        Some(bert) => {
+            eprintln!("   [AI] 正在分析文档语义...");
+            let keywords = inference.run(|| bert.extract_keywords(&doc_data.content, config.ai.tags_per_doc))?;
+            eprintln!("   [AI] 生成标签: {:?}", keywords);
+            keywords.join(" ") // 变成 "Rust 编程 教程" 这样的字符串存入
+        }
+        None => String::new(),
+    };
     // ---------------------------
 
+    Ok(PreparedDoc { title: doc_data.title, path: doc_data.path, content: doc_data.content, tags, timestamp })
+}
+
+fn indexed_event(doc_data: &PreparedDoc) -> EngineEvent {
+    EngineEvent::FileIndexed {
+        path: doc_data.path.clone(),
+        title: doc_data.title.clone(),
+        tags: doc_data.tags.split_whitespace().map(|t| t.to_string()).collect(),
+    }
+}
+
+fn failed_event(path: &Path, e: &anyhow::Error) -> EngineEvent {
+    EngineEvent::FileFailed { path: path.to_string_lossy().to_string(), error: format!("{:#}", e) }
+}
+
+// 写入 writer (先删旧文档再加新文档)，不提交
+fn write_document(doc_data: &PreparedDoc, writer: &IndexWriter, schema: &Schema) -> Result<()> {
     let title_field = schema.get_field("title").unwrap();
     let body_field = schema.get_field("body").unwrap();
     let path_field = schema.get_field("path").unwrap();
     let tags_field = schema.get_field("tags").unwrap();
     let timestamp_field = schema.get_field("timestamp").unwrap();
-    // 每次创建 writer 开销较大，但在 Watcher 这种低频场景下是可以接受的
-    let mut index_writer: IndexWriter = index.writer(50_000_000)?;
 
     // 先删除旧的
     let path_term = Term::from_field_text(path_field, &doc_data.path);
-    index_writer.delete_term(path_term);
+    writer.delete_term(path_term);
 
     // 写入新的
-    index_writer.add_document(doc!(
+    writer.add_document(doc!(
         title_field => doc_data.title.as_str(),
         body_field => doc_data.content.as_str(),
         path_field => doc_data.path.as_str(),
-        tags_field => tags_str, // <--- 存入 AI 生成的标签
-        timestamp_field => file_timestamp // 写入时间戳
+        tags_field => doc_data.tags.as_str(), // <--- 存入 AI 生成的标签
+        timestamp_field => doc_data.timestamp // 写入时间戳
     ))?;
 
-    index_writer.commit()?;
+    Ok(())
+}
+
+// 处理单个文件并立即提交 (改为 pub 供 watcher 使用)
+// bert 为 None 时 (AI 已禁用) 不生成标签
+pub fn process_and_index(file_path: &Path, index: &Index, schema: &Schema, bert: Option<&dyn Tagger>, config: &Config, events: &EventBus) -> Result<()> {
+    let indexed = prepare_document(file_path, bert, config, &InferenceLimit::new(1)).and_then(|prepared| {
+        // 每次创建 writer 开销较大，但在 Watcher 这种低频场景下是可以接受的
+        let mut index_writer: IndexWriter = index.writer(config.index.heap_size)?;
+        write_document(&prepared, &index_writer, schema)?;
+        index_writer.commit()?;
+        Ok(prepared)
+    });
+    let prepared = match indexed {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            events.publish(failed_event(file_path, &e));
+            return Err(e);
+        }
+    };
+    events.publish(indexed_event(&prepared));
 
-    println!("\n[Done] [后台] 新文件已索引: {} (输入搜索词继续)", doc_data.title);
+    println!("\n[Done] [后台] 新文件已索引: {} (输入搜索词继续)", prepared.title);
     print!("> ");
     io::stdout().flush()?;
 
     Ok(())
 }
 
-// 扫描现有文件
-pub fn scan_existing_files(watch_path: &Path, index: &Index, schema: &Schema, bert: &BertModel) -> Result<()> {
+// 外部请求要索引的文件为什么被拒绝
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathRejection {
+    OutsideRoots,
+    NotFound,
+    Filtered,
+}
+
+impl std::fmt::Display for PathRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            PathRejection::OutsideRoots => "不在任何监控目录下",
+            PathRejection::NotFound => "文件不存在",
+            PathRejection::Filtered => "被配置的扩展名、排除路径或大小限制过滤掉了",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for PathRejection {}
+
+// 外部请求 (HTTP / MCP) 只能索引监控目录下、符合过滤规则的文件，
+// 否则任何能调用接口的人都能借搜索结果读到机器上的任意文件；返回扫描时的路径写法
+pub fn resolve_external_path(path: &Path, config: &Config) -> std::result::Result<PathBuf, PathRejection> {
+    let Some(normalized) = config.watch.normalize_path(path) else {
+        // 不存在的文件没法 canonicalize；只有按字面也在监控目录下时才说"不存在"，
+        // 免得调用方借错误类型探测目录外有哪些文件
+        let missing_inside_roots = !path.exists() && config.watch.root_for(path).is_some();
+        return Err(if missing_inside_roots { PathRejection::NotFound } else { PathRejection::OutsideRoots });
+    };
+    if !normalized.is_file() {
+        return Err(PathRejection::NotFound);
+    }
+    if !config.rules_for(&normalized).accepts(&normalized) {
+        return Err(PathRejection::Filtered);
+    }
+    Ok(normalized)
+}
+
+// 索引一批指定的文件 (HTTP / MCP 接口用)，共用一个 writer，和扫描一样每 commit_every_docs 个文件提交一次；
+// 返回每个文件的标题或错误
+pub fn index_paths(paths: &[PathBuf], index: &Index, schema: &Schema, bert: Option<&dyn Tagger>, config: &Config, events: &EventBus) -> Result<Vec<Result<String>>> {
+    let inference = InferenceLimit::new(config.scan.inference_parallelism);
+    let mut index_writer: IndexWriter = index.writer(config.index.heap_size)?;
+    let mut results = Vec::with_capacity(paths.len());
+    // 已写入但还没提交的文件，提交之后再发布事件，收到 file_indexed 时文件已经可以搜到了
+    let mut uncommitted: Vec<EngineEvent> = Vec::new();
+
+    for path in paths {
+        let prepared = prepare_document(path, bert, config, &inference)
+            .and_then(|prepared| write_document(&prepared, &index_writer, schema).map(|_| prepared));
+        match prepared {
+            Ok(prepared) => {
+                uncommitted.push(indexed_event(&prepared));
+                results.push(Ok(prepared.title));
+            }
+            Err(e) => {
+                events.publish(failed_event(path, &e));
+                results.push(Err(e));
+            }
+        }
+
+        if uncommitted.len() >= config.index.commit_every_docs {
+            index_writer.commit()?;
+            uncommitted.drain(..).for_each(|event| events.publish(event));
+        }
+    }
+
+    if !uncommitted.is_empty() {
+        index_writer.commit()?;
+        uncommitted.drain(..).for_each(|event| events.publish(event));
+    }
+    Ok(results)
+}
+
+// 从索引中删除一个文件；索引里没有这个路径时返回 false
+pub fn delete_document(index: &Index, schema: &Schema, path: &str, config: &Config, events: &EventBus) -> Result<bool> {
+    if get_document(index, path)?.is_none() {
+        return Ok(false);
+    }
+
+    let path_field = schema.get_field("path").unwrap();
+    let mut index_writer: IndexWriter = index.writer(config.index.heap_size)?;
+    index_writer.delete_term(Term::from_field_text(path_field, path));
+    index_writer.commit()?;
+    events.publish(EngineEvent::FileDeleted { path: path.to_string() });
+    Ok(true)
+}
+
+// 扫描所有监控目录下的现有文件：
+// scan.parallelism 个线程并行提取文本和生成标签 (其中最多 inference_parallelism 个同时推理)，
+// 主线程用同一个 writer 写入，每 commit_every_docs 个文件提交一次
+// stop 被置位后 (Ctrl-C)，处理完手头的文件就停止扫描，已处理的文件会做最后一次提交
+pub fn scan_existing_files(roots: &[PathBuf], index: &Index, schema: &Schema, bert: Option<&dyn Tagger>, config: &ConfigHandle, events: &EventBus, stop: &AtomicBool) -> Result<()> {
     println!(" [后台] 正在扫描现有文件...");
+    let pending = collect_pending_files(roots, index, schema, &config.current(), stop);
+    let pending_count = pending.len();
+    events.publish(EngineEvent::ScanStarted { roots: roots.to_vec(), pending: pending_count });
+
+    // 线程数和写入内存在扫描开始时确定；过滤、打标签和提交间隔每个文件都取一次最新配置
+    let startup = config.current();
+    let mut index_writer: IndexWriter = index.writer(startup.index.heap_size)?;
+    let workers = startup.scan.parallelism.clamp(1, pending.len().max(1));
+    let inference = InferenceLimit::new(startup.scan.inference_parallelism);
+    let queue = Mutex::new(pending.into_iter());
     let mut file_count = 0;
+    let mut processed = 0;
+    // 已写入但还没提交的文件，提交后再发布 file_indexed 事件
+    let mut uncommitted: Vec<EngineEvent> = Vec::new();
 
-    fn visit_dirs(dir: &Path, index: &Index, schema: &Schema, file_count: &mut usize, bert: &BertModel) -> Result<()> {
-        if dir.is_dir() {
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    visit_dirs(&path, index, schema, file_count, bert)?;
-                } else if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        let ext = extension.to_string_lossy().to_lowercase();
-                        if matches!(ext.as_str(), "txt" | "md" | "pdf") {
-                             if !path.to_string_lossy().contains(".DS_Store") {
-                                
-                                // 增加判断逻辑
-                                if should_index_file(&path, index, schema) {
-                                    // 只有需要更新时，才执行繁重的 AI 和索引任务
-                                    match process_and_index(&path, index, schema, bert) {
-                                        Ok(_) => *file_count += 1,
-                                        Err(e) => eprintln!("处理文件失败 {:?}: {}", path, e),
-                                    }
-                                } else {
-                                    // 否则跳过
-                                    // println!(" [跳过] 文件未修改: {:?}", path.file_name().unwrap());
-                                }
-                                
-                             }
-                        }
+    thread::scope(|scope| -> Result<()> {
+        let (tx, rx) = channel();
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let (queue, inference) = (&queue, &inference);
+            scope.spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let Some(path) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    // 每个文件都取一次最新配置，扫描过程中 reload 的设置也能立即生效
+                    let prepared = prepare_document(&path, bert, &config.current(), inference);
+                    if tx.send((path, prepared)).is_err() {
+                        break;
                     }
                 }
+            });
+        }
+        drop(tx);
+
+        for (path, prepared) in rx {
+            processed += 1;
+            match prepared.and_then(|doc_data| write_document(&doc_data, &index_writer, schema).map(|_| doc_data)) {
+                Ok(doc_data) => {
+                    println!(" [后台] 已索引: {}", doc_data.title);
+                    file_count += 1;
+                    uncommitted.push(indexed_event(&doc_data));
+                }
+                Err(e) => {
+                    eprintln!("处理文件失败 {:?}: {}", path, e);
+                    events.publish(failed_event(&path, &e));
+                }
+            }
+
+            if uncommitted.len() >= config.current().index.commit_every_docs {
+                index_writer.commit()?;
+                uncommitted.drain(..).for_each(|event| events.publish(event));
+                events.publish(EngineEvent::ScanProgress { processed, pending: pending_count });
             }
         }
         Ok(())
+    })?;
+
+    if !uncommitted.is_empty() {
+        index_writer.commit()?;
+        uncommitted.drain(..).for_each(|event| events.publish(event));
     }
 
-    visit_dirs(watch_path, index, schema, &mut file_count, bert)?;
-    println!(" [后台] 初始索引完成，共处理 {} 个文件", file_count);
+    let interrupted = stop.load(Ordering::SeqCst);
+    if interrupted {
+        println!(" [后台] 扫描已中断，已处理 {} 个文件", file_count);
+    } else {
+        println!(" [后台] 初始索引完成，共处理 {} 个文件", file_count);
+    }
+    events.publish(EngineEvent::ScanFinished { indexed: file_count, interrupted });
     Ok(())
 }
 
+// 遍历监控目录，找出需要 (重新) 索引的文件
+fn collect_pending_files(roots: &[PathBuf], index: &Index, schema: &Schema, config: &Config, stop: &AtomicBool) -> Vec<PathBuf> {
+    let mut pending = Vec::new();
+
+    'scan: for root in roots {
+        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if stop.load(Ordering::SeqCst) {
+                break 'scan;
+            }
+            let path = entry.path();
+            // 嵌套的监控目录只由最内层的那个负责，避免同一个文件被扫描两次
+            if config.watch.root_for(path).is_some_and(|owner| &owner.path != root) {
+                continue;
+            }
+            // 只有需要更新时，才执行繁重的 AI 和索引任务
+            if entry.file_type().is_file() && config.rules_for(path).accepts(path) && should_index_file(path, index, schema) {
+                pending.push(path.to_path_buf());
+            }
+        }
+    }
+    pending
+}
+
+// 监控线程的句柄，用于退出时通知线程停止并等待它处理完手头的文件
+pub struct WatcherHandle {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl WatcherHandle {
+    pub fn stop_and_join(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if self.thread.join().is_err() {
+            eprintln!("监控线程异常退出");
+        }
+    }
+}
+
 // 启动监控线程
-pub fn start_watcher_thread(watch_path: PathBuf, index: Index, schema: Schema, bert: Arc<BertModel>) {
-    thread::spawn(move || {
+// config 是和 AppState 共享的句柄，reload 之后的过滤规则对下一个事件立即生效
+pub fn start_watcher_thread(roots: Vec<PathBuf>, index: Index, schema: Schema, bert: Option<Arc<dyn Tagger>>, config: ConfigHandle, events: Arc<EventBus>) -> WatcherHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_for_thread = stop.clone();
+
+    let thread = thread::spawn(move || {
         let (tx, rx) = channel();
-        let mut watcher = RecommendedWatcher::new(tx, Config::default()).unwrap();
+        let mut watcher = RecommendedWatcher::new(tx, NotifyConfig::default()).unwrap();
         // 使用文件修改时间而不是处理时间戳来判断文件是否真的变化了
         let mut file_mod_times: HashMap<PathBuf, std::time::SystemTime> = HashMap::new();
 
-        if let Err(e) = watcher.watch(&watch_path, RecursiveMode::Recursive) {
-            eprintln!("监控启动失败: {:?}", e);
-            return;
+        for root in &roots {
+            if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+                eprintln!("监控启动失败 {:?}: {:?}", root, e);
+                return;
+            }
         }
 
-        for res in rx {
+        // 定时醒来检查停止标志，而不是一直阻塞在 rx 上
+        loop {
+            if stop_for_thread.load(Ordering::SeqCst) {
+                break;
+            }
+            let res = match rx.recv_timeout(Duration::from_millis(200)) {
+                Ok(res) => res,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
             match res {
                 Ok(event) => {
                     match event.kind {
                         EventKind::Create(_) | EventKind::Modify(_) => {
                             for path in event.paths {
-                                if path.is_file() && !path.to_string_lossy().contains(".DS_Store") {
-                                    // 检查文件扩展名
-                                    if let Some(extension) = path.extension() {
-                                        let ext = extension.to_string_lossy().to_lowercase();
-                                        if matches!(ext.as_str(), "txt" | "md" | "pdf") {
-                                            // 检查文件修改时间是否真的发生了变化
-                                            if let Ok(metadata) = fs::metadata(&path) {
-                                                if let Ok(modified) = metadata.modified() {
-                                                    let should_process = match file_mod_times.get(&path) {
-                                                        Some(&last_mod) => modified != last_mod,
-                                                        None => true, // 新文件
-                                                    };
-
-                                                    if should_process {
-                                                        file_mod_times.insert(path.clone(), modified);
-                                                        // 等待文件写入完成
-                                                        thread::sleep(Duration::from_millis(500));
-                                                        let _ = process_and_index(&path, &index, &schema, &bert);
-                                                    }
-                                                }
-                                            }
-                                        }
+                                // 扩展名、排除路径、文件大小按文件所属监控目录的规则过滤
+                                let config = config.current();
+                                if path.is_file() && config.rules_for(&path).accepts(&path) {
+                                    // 检查文件修改时间是否真的发生了变化
+                                    let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else {
+                                        continue;
+                                    };
+                                    let should_process = match file_mod_times.get(&path) {
+                                        Some(&last_mod) => modified != last_mod,
+                                        None => true, // 新文件
+                                    };
+
+                                    if should_process {
+                                        file_mod_times.insert(path.clone(), modified);
+                                        // 等待文件写入完成
+                                        thread::sleep(Duration::from_millis(config.watch.debounce_ms));
+                                        let _ = process_and_index(&path, &index, &schema, bert.as_deref(), &config, &events);
                                     }
                                 }
                             }
                         },
+                        EventKind::Remove(_) => {
+                            for path in event.paths {
+                                file_mod_times.remove(&path);
+                                // 文件已经不在了，没法再按扩展名过滤；索引里没有这个路径时什么也不做
+                                if let Err(e) = delete_document(&index, &schema, &path.to_string_lossy(), &config.current(), &events) {
+                                    eprintln!("删除索引失败 {:?}: {}", path, e);
+                                }
+                            }
+                        },
                         _ => {},
                     }
                 },
@@ -246,4 +523,227 @@ pub fn start_watcher_thread(watch_path: PathBuf, index: Index, schema: Schema, b
             }
         }
     });
-}
\ No newline at end of file
+
+    WatcherHandle { stop, thread }
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::RootConfig;
+
+    // 不加载模型的标签生成器：记下收到的每段文本，返回固定的标签
+    #[derive(Default)]
+    pub(crate) struct CountingTagger {
+        pub(crate) texts: Mutex<Vec<String>>,
+    }
+
+    impl Tagger for CountingTagger {
+        fn extract_keywords(&self, text: &str, _top_k: usize) -> Result<Vec<String>> {
+            self.texts.lock().unwrap().push(text.to_string());
+            Ok(vec!["桩标签".to_string()])
+        }
+    }
+
+    // 轮询等待条件成立，最多等 10 秒
+    fn wait_until(mut condition: impl FnMut() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        false
+    }
+
+    fn watch_config(root: &Path, extensions: &[&str]) -> Config {
+        let mut config = Config::default();
+        config.watch.roots = vec![RootConfig::new(root)];
+        config.watch.extensions = extensions.iter().map(|ext| ext.to_string()).collect();
+        config.watch.debounce_ms = 0;
+        config.index.heap_size = crate::config::MIN_WRITER_HEAP_SIZE;
+        config
+    }
+
+    fn indexed(index: &Index, path: &Path) -> bool {
+        get_document(index, &path.to_string_lossy()).unwrap().is_some()
+    }
+
+    #[test]
+    fn watcher_picks_up_extensions_added_by_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let (index, schema) = init_ram_index();
+        let config = ConfigHandle::new(watch_config(&root, &["txt"]));
+        let watcher = start_watcher_thread(vec![root.clone()], index.clone(), schema, None, config.clone(), Arc::default());
+        // 等 watcher 注册完监控再写文件
+        thread::sleep(Duration::from_millis(300));
+
+        // 先写 .rs，再写 .txt；事件按顺序处理，.txt 入库时 .rs 已经被过滤掉了
+        let code = root.join("main.rs");
+        fs::write(&code, "fn main() {}").unwrap();
+        let note = root.join("note.txt");
+        fs::write(&note, "监控目录里的笔记").unwrap();
+        assert!(wait_until(|| indexed(&index, &note)));
+        assert!(!indexed(&index, &code));
+
+        config.reload(Arc::new(watch_config(&root, &["txt", "rs"]))).unwrap();
+        let code = root.join("lib.rs");
+        fs::write(&code, "pub fn search() {}").unwrap();
+        assert!(wait_until(|| indexed(&index, &code)));
+
+        watcher.stop_and_join();
+    }
+
+    fn segment_count(index: &Index) -> usize {
+        index.searchable_segment_metas().unwrap().len()
+    }
+
+    #[test]
+    fn index_paths_commits_every_n_docs() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (0..3).map(|i| dir.path().join(format!("{}.txt", i))).collect();
+        for path in &paths {
+            fs::write(path, "批量索引").unwrap();
+        }
+        let mut config = watch_config(dir.path(), &["txt"]);
+
+        // 每次提交生成一个新段，段数就是提交次数
+        config.index.commit_every_docs = 1;
+        let (index, schema) = init_ram_index();
+        let results = index_paths(&paths, &index, &schema, None, &config, &EventBus::default()).unwrap();
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(segment_count(&index), 3);
+
+        config.index.commit_every_docs = 2;
+        let (index, schema) = init_ram_index();
+        index_paths(&paths, &index, &schema, None, &config, &EventBus::default()).unwrap();
+        assert_eq!(segment_count(&index), 2);
+        assert_eq!(index.reader().unwrap().searcher().num_docs(), 3);
+    }
+
+    #[test]
+    fn scan_reads_commit_and_parallelism_knobs() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "初始扫描").unwrap();
+        }
+        let roots = vec![dir.path().to_path_buf()];
+        let scan = |commit_every_docs: usize, parallelism: usize| {
+            let mut config = watch_config(dir.path(), &["txt"]);
+            config.index.commit_every_docs = commit_every_docs;
+            config.scan.parallelism = parallelism;
+            let (index, schema) = init_ram_index();
+            scan_existing_files(&roots, &index, &schema, None, &ConfigHandle::new(config), &EventBus::default(), &AtomicBool::new(false)).unwrap();
+            assert_eq!(index.reader().unwrap().searcher().num_docs(), 4);
+            segment_count(&index)
+        };
+
+        assert_eq!(scan(1, 1), 4);
+        assert_eq!(scan(1, 4), 4);
+        assert_eq!(scan(3, 2), 2);
+        assert_eq!(scan(100, 2), 1);
+    }
+
+    #[test]
+    fn scan_tags_only_files_under_roots_with_ai_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let (notes, code) = (dir.path().join("notes"), dir.path().join("code"));
+        fs::create_dir(&notes).unwrap();
+        fs::create_dir(&code).unwrap();
+        for i in 0..2 {
+            fs::write(notes.join(format!("{}.txt", i)), format!("笔记 {}", i)).unwrap();
+        }
+        for i in 0..3 {
+            fs::write(code.join(format!("{}.txt", i)), format!("代码 {}", i)).unwrap();
+        }
+        let mut config = watch_config(&notes, &["txt"]);
+        let mut code_root = RootConfig::new(&code);
+        code_root.ai_tags = Some(false);
+        config.watch.roots.push(code_root);
+
+        let tagger = CountingTagger::default();
+        let (index, schema) = init_ram_index();
+        let roots = config.watch.root_paths();
+        scan_existing_files(&roots, &index, &schema, Some(&tagger), &ConfigHandle::new(config), &EventBus::default(), &AtomicBool::new(false)).unwrap();
+
+        // 只有 notes 下的文件送去生成了标签
+        let mut texts = tagger.texts.lock().unwrap().clone();
+        texts.sort();
+        assert_eq!(texts, ["笔记 0", "笔记 1"]);
+
+        let tags = |path: PathBuf| get_document(&index, &path.to_string_lossy()).unwrap().unwrap().tags;
+        for i in 0..2 {
+            assert_eq!(tags(notes.join(format!("{}.txt", i))), ["桩标签"]);
+        }
+        for i in 0..3 {
+            assert!(tags(code.join(format!("{}.txt", i))).is_empty());
+        }
+    }
+
+    #[test]
+    fn scan_publishes_started_indexed_finished_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "扫描事件").unwrap();
+        let config = watch_config(dir.path(), &["txt"]);
+        let roots = config.watch.root_paths();
+
+        let events = EventBus::default();
+        let subscription = events.subscribe();
+        let (index, schema) = init_ram_index();
+        scan_existing_files(&roots, &index, &schema, None, &ConfigHandle::new(config), &events, &AtomicBool::new(false)).unwrap();
+
+        let received: Vec<EngineEvent> = std::iter::from_fn(|| subscription.recv_timeout(Duration::from_millis(100))).collect();
+        assert_eq!(
+            received,
+            [
+                EngineEvent::ScanStarted { roots, pending: 1 },
+                EngineEvent::FileIndexed { path: path.to_string_lossy().to_string(), title: "a".to_string(), tags: Vec::new() },
+                EngineEvent::ScanFinished { indexed: 1, interrupted: false },
+            ]
+        );
+    }
+
+    // 监控目录 root 下有 a.txt，目录外有 secret.txt
+    fn external_fixture() -> (tempfile::TempDir, PathBuf, Config) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("a.txt"), "监控目录里的文件").unwrap();
+        fs::write(dir.path().join("secret.txt"), "目录外的文件").unwrap();
+        let config = watch_config(&root, &["txt"]);
+        (dir, root, config)
+    }
+
+    #[test]
+    fn external_path_is_normalized_to_root_spelling() {
+        let (_dir, root, config) = external_fixture();
+        fs::create_dir(root.join("sub")).unwrap();
+        let spelled = root.join(".").join("sub").join("..").join("a.txt");
+        assert_eq!(resolve_external_path(&spelled, &config), Ok(root.join("a.txt")));
+        assert_eq!(resolve_external_path(&root.join("missing.txt"), &config), Err(PathRejection::NotFound));
+    }
+
+    #[test]
+    fn external_path_with_parent_dir_cannot_escape_root() {
+        let (dir, root, config) = external_fixture();
+        let escaping = root.join("..").join("secret.txt");
+        assert!(escaping.is_file());
+        assert_eq!(resolve_external_path(&escaping, &config), Err(PathRejection::OutsideRoots));
+        // 按字面看在目录里、实际不存在的路径也不能拿来探测目录外的文件
+        assert_eq!(resolve_external_path(&dir.path().join("missing.txt"), &config), Err(PathRejection::OutsideRoots));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_path_through_symlink_cannot_escape_root() {
+        let (dir, root, config) = external_fixture();
+        let file_link = root.join("link.txt");
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), &file_link).unwrap();
+        assert_eq!(resolve_external_path(&file_link, &config), Err(PathRejection::OutsideRoots));
+
+        let dir_link = root.join("outside");
+        std::os::unix::fs::symlink(dir.path(), &dir_link).unwrap();
+        assert_eq!(resolve_external_path(&dir_link.join("secret.txt"), &config), Err(PathRejection::OutsideRoots));
+    }
+}
@@ -5,6 +5,20 @@ pub mod extract;
 pub mod search;
 pub mod indexer;
 pub mod ai; // <--- 新增这一行
+pub mod cli;
+pub mod doctor;
+pub mod app;
+pub mod events;
+pub mod api;
+pub mod rpc;
+pub mod tui;
+pub mod repl;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 
 pub use config::*;
 pub use models::*;
@@ -12,3 +26,8 @@ pub use extract::*;
 pub use search::*;
 pub use indexer::*;
 pub use ai::*; // <--- 新增这一行
+pub use cli::*;
+pub use doctor::*;
+pub use app::*;
+pub use events::*;
+pub use api::*;
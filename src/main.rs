@@ -1,67 +1,260 @@
 // main.rs
 use std::io::{self, Write};
-use std::path::Path;
 use anyhow::Result;
 use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
+use ai_search_demo::app::{self, AppState};
 use ai_search_demo::indexer;
+use ai_search_demo::repl::{self, ReplCommand, ReplState, ReplStep};
 use ai_search_demo::search;
-use ai_search_demo::config;
-use ai_search_demo::ai::BertModel;
+use ai_search_demo::tui;
+use ai_search_demo::config::{Config, ConfigHandle};
+use ai_search_demo::doctor;
+use ai_search_demo::ai::{BertModel, Tagger};
+use ai_search_demo::rpc::{self, RpcServer};
+use ai_search_demo::cli::{self, Cli, Command, ServeArgs};
 
 
 fn main() -> Result<()> {
+    let cli = cli::parse_args(std::env::args().skip(1))?;
+    match &cli.command {
+        // doctor 自己检查配置文件，配置有问题时也要能跑完其余检查
+        Command::Doctor { deep } => {
+            let results = doctor::run_all(cli.config_path.as_deref(), &cli.overrides, *deep);
+            println!("{}", doctor::render_report(&results));
+            if doctor::has_failures(&results) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Tags(args) => {
+            // 一次性命令：只读索引，不需要加载 BERT
+            let config = load_config(&cli)?;
+            let (index, _schema) = indexer::init_persistent_index(&config.index.storage_path)?;
+            println!("{}", cli::run_tags(&index, args)?);
+            Ok(())
+        }
+        Command::Tui => {
+            // TUI 直接搜索已有索引，不启动监控，也不加载模型
+            let config = load_config(&cli)?;
+            let (index, _schema) = indexer::init_persistent_index(&config.index.storage_path)?;
+            tui::run(index)
+        }
+        Command::ConfigShow { defaults } => {
+            let config = if *defaults { Config::default() } else { cli.load_config()? };
+            print!("{}", config.to_toml_string()?);
+            // 问题打印到 stderr，不影响把输出重定向成配置文件
+            for problem in config.validate() {
+                eprintln!(" [配置] {}", problem);
+            }
+            Ok(())
+        }
+        Command::Serve(args) => run_server(load_config(&cli)?, args),
+        Command::Grpc(args) => run_grpc(load_config(&cli)?, args),
+        Command::Mcp => run_mcp(load_config(&cli)?),
+        Command::Rpc => {
+            // 和 MCP 一样只打开索引，模型等到第一次 index_file 才加载
+            let config = load_config(&cli)?;
+            let (index, schema) = indexer::init_persistent_index(&config.index.storage_path)?;
+            let server = RpcServer::new(index, schema, config);
+            rpc::serve_stdio(&server)
+        }
+        Command::Interactive => run_interactive(&cli, load_config(&cli)?),
+    }
+}
 
-    println!(" [AI] 正在加载 BERT 模型 (首次运行需下载)...");
-    // 初始化 BERT，并用 Arc 包裹以便在多线程共享
-    let bert = Arc::new(BertModel::new()?); 
-    println!(" [AI] 模型加载完毕！");
+// 加载配置文件 (叠加命令行覆盖)，校验不通过时拒绝启动；
+// 加载好的配置登记为全局配置，start_app 让 AppState 共用同一个句柄
+fn load_config(cli: &Cli) -> Result<Arc<Config>> {
+    let config = Arc::new(cli.load_config()?.validated()?);
+    Config::set_global(ConfigHandle::new(config.clone()))?;
+    Ok(config)
+}
 
-    let watch_path = Path::new(config::WATCH_PATH);
-    let storage_path = Path::new(config::STORAGE_PATH);
+// 加载模型、打开索引 (交互模式、HTTP 和 gRPC 服务共用)；
+// 扫描现有文件和启动监控由调用方在合适的时机调用 start_indexing
+fn start_app(config: Arc<Config>) -> Result<Arc<AppState>> {
+    let ai = &config.ai;
 
-    if !watch_path.exists() { std::fs::create_dir_all(watch_path)?; }
+    // AI 被禁用 (--no-ai 或配置 ai.enabled = false) 时完全跳过模型加载，也就不会触发下载
+    let bert: Option<Arc<dyn Tagger>> = if ai.enabled {
+        println!(" [AI] 正在加载 BERT 模型 (首次运行需下载)...");
+        // 初始化 BERT，并用 Arc 包裹以便在多线程共享
+        let bert = Arc::new(BertModel::load(ai)?);
+        println!(" [AI] 模型加载完毕！");
+        Some(bert)
+    } else {
+        None
+    };
+
+    let roots = config.watch.root_paths();
+    for root in &roots {
+        if !root.exists() { std::fs::create_dir_all(root)?; }
+    }
 
     println!("--- 文件搜索系统 ---");
-    println!(" [后台] 正在监控: {:?}", watch_path);
-    println!(" [前台] 输入关键词进行搜索 (输入 'quit' 退出)");
+    println!(" [AI] {}", ai.describe());
+    println!(" [后台] 正在监控: {:?}", roots);
 
    // 1. 初始化索引 (schema 里现在有 tags 字段了)
-    let (index, schema) = indexer::init_persistent_index(storage_path)?;
+    let (index, schema) = indexer::init_persistent_index(&config.index.storage_path)?;
+    let app = Arc::new(AppState::new(index, schema, bert, Config::global_handle().clone()));
+
+    // Ctrl-C 只置位退出标志，扫描和主循环看到标志后走统一的 shutdown 流程
+    app::install_ctrlc_handler(app.shutdown_flag())?;
+    Ok(app)
+}
+
+// 服务模式先绑定端口，再开始后台扫描
+#[cfg(any(feature = "server", feature = "grpc"))]
+fn bind(addr: std::net::SocketAddr) -> Result<std::net::TcpListener> {
+    std::net::TcpListener::bind(addr).map_err(|e| anyhow::anyhow!("无法监听 {}: {}", addr, e))
+}
+
+#[cfg(feature = "server")]
+fn run_server(config: Arc<Config>, args: &ServeArgs) -> Result<()> {
+    let app = start_app(config)?;
+    let listener = bind(args.addr)?;
+    // 2. 后台扫描现有文件，扫描完启动监控
+    app.start_indexing();
+    let served = ai_search_demo::server::serve(app.clone(), listener, args.token.clone());
+    app.shutdown()?;
+    served
+}
+
+#[cfg(not(feature = "server"))]
+fn run_server(_config: Arc<Config>, _args: &ServeArgs) -> Result<()> {
+    Err(anyhow::anyhow!("当前程序编译时没有启用 HTTP 服务，请用 cargo build --features server 重新编译"))
+}
+
+#[cfg(feature = "grpc")]
+fn run_grpc(config: Arc<Config>, args: &ServeArgs) -> Result<()> {
+    let app = start_app(config)?;
+    let listener = bind(args.addr)?;
+    app.start_indexing();
+    let served = ai_search_demo::grpc::serve(app.clone(), listener, args.token.clone());
+    app.shutdown()?;
+    served
+}
+
+#[cfg(not(feature = "grpc"))]
+fn run_grpc(_config: Arc<Config>, _args: &ServeArgs) -> Result<()> {
+    Err(anyhow::anyhow!("当前程序编译时没有启用 gRPC 服务，请用 cargo build --features grpc 重新编译"))
+}
 
-    // 2. 扫描现有文件 (传入 bert)
-    indexer::scan_existing_files(watch_path, &index, &schema, &bert)?;
+// MCP 服务和一次性命令一样只打开索引：不扫描、不监控，模型等到第一次 index_path 才加载
+#[cfg(feature = "mcp")]
+fn run_mcp(config: Arc<Config>) -> Result<()> {
+    let (index, schema) = indexer::init_persistent_index(&config.index.storage_path)?;
+    let server = ai_search_demo::mcp::McpServer::new(index, schema, config);
+    ai_search_demo::mcp::serve_stdio(&server)
+}
 
-    // 3. 启动后台监控 (传入 bert)
-    let index_for_watcher = index.clone();
-    let schema_for_watcher = schema.clone();
-    let bert_for_watcher = bert.clone(); // Arc 克隆，只是引用计数+1
-    indexer::start_watcher_thread(watch_path.to_path_buf(), index_for_watcher, schema_for_watcher, bert_for_watcher);
+#[cfg(not(feature = "mcp"))]
+fn run_mcp(_config: Arc<Config>) -> Result<()> {
+    Err(anyhow::anyhow!("当前程序编译时没有启用 MCP 服务，请用 cargo build --features mcp 重新编译"))
+}
+
+fn run_interactive(cli: &Cli, config: Arc<Config>) -> Result<()> {
+    let app = start_app(config)?;
+    // 2. 后台扫描现有文件，扫描完启动监控；扫描期间已经可以搜索
+    app.start_indexing();
+    println!(" [前台] 输入关键词进行搜索 (输入 'quit' 退出)");
+    println!(" [前台] 翻页: n 下一页 / p 上一页 / page 4 跳页 / limit 50 每页条数 / type pdf 只看某类文件 (type all 取消)");
+    println!(" [前台] 修改配置文件后输入 reload 重新加载 (扩展名、排除路径等无需重启)");
 
     // 4. 主线程循环：处理用户输入并调用 search 模块
-    loop {
+    // stdin 放到单独线程里读，这样主循环能及时响应 Ctrl-C
+    let (line_tx, line_rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        loop {
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => break, // EOF
+                Ok(_) => {
+                    if line_tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut repl_state = ReplState::default();
+    while !app.is_shutting_down() {
         print!("> ");
         io::stdout().flush()?;
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim();
-
-        if input == "quit" || input == "exit" {
+        let Some(input) = wait_for_line(&app, &line_rx) else {
             break;
-        }
-        if input.is_empty() {
-            continue;
-        }
-        //bert 来优化查询
-        let search_query = bert.refine_query(input);
+        };
+
+        let command = match repl::parse_command(&input) {
+            //bert 来优化查询 (只在输入新查询时做一次，翻页沿用优化后的查询)
+            ReplCommand::Search(query) => match &app.bert {
+                Some(bert) => ReplCommand::Search(bert.refine_query(&query)),
+                None => ReplCommand::Search(query),
+            },
+            other => other,
+        };
 
-        // 调用 lib 里的 search 模块进行搜索
-        // 注意：Tantivy 的 Reader 会自动感知 index 的变化，所以这里不需要手动 reload
-        if let Err(e) = search::search_index(&index, &search_query) {
-            println!("搜索出错: {}", e);
+        let mut step = repl_state.apply(command);
+        loop {
+            match step {
+                ReplStep::Quit => return app.shutdown(),
+                ReplStep::Nothing => break,
+                ReplStep::Message(message) => {
+                    println!("   {}", message);
+                    break;
+                }
+                ReplStep::Reload => {
+                    // 重新叠加命令行覆盖，避免 --no-ai 等被配置文件覆盖后误判为需要重启
+                    match cli.load_config().and_then(Config::validated).and_then(|config| app.reload_config(Arc::new(config))) {
+                        Ok(()) => println!("   配置已重新加载: {:?}", cli.config_file()),
+                        Err(e) => println!("   {:#}", e),
+                    }
+                    break;
+                }
+                ReplStep::Run { query, options } => {
+                    // 调用 lib 里的 search 模块进行搜索
+                    // 注意：Tantivy 的 Reader 会自动感知 index 的变化，所以这里不需要手动 reload
+                    match search::search_page(&app.index, &query, &options) {
+                        Ok(page) => {
+                            step = repl_state.record_total(page.total);
+                            // 当前页超出末尾时会得到一次末页的重新搜索，这一页就不打印了
+                            if step == ReplStep::Nothing {
+                                search::print_search_page(&page);
+                                if page.total > 0 {
+                                    println!("   {}", repl_state.page_summary());
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            println!("   {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
         }
     }
 
-    Ok(())
+    app.shutdown()
+}
+
+// 等待下一行输入；收到退出信号或 stdin 关闭时返回 None
+fn wait_for_line(app: &AppState, line_rx: &Receiver<String>) -> Option<String> {
+    loop {
+        if app.is_shutting_down() {
+            return None;
+        }
+        match line_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(line) => return Some(line),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,374 @@
+// mcp.rs
+// MCP (Model Context Protocol) 服务 (编译时需要 --features mcp)：让 LLM agent 把本地索引当作工具调用。
+// 协议是 stdio 上逐行的 JSON-RPC 2.0，stdout 只能输出协议消息，所有日志都走 stderr
+use std::io::{BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tantivy::Index;
+use tantivy::schema::Schema;
+
+use crate::ai::LazyBert;
+use crate::events::EventBus;
+use crate::config::Config;
+use crate::indexer;
+use crate::api::SearchRequest;
+use crate::rpc::{self, INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND, Request, error_response, success_response};
+use crate::search;
+
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+const MAX_SEARCH_LIMIT: usize = 50;          // 一次最多返回的结果数，避免撑爆 agent 的上下文
+const DEFAULT_DOCUMENT_CHARS: usize = 8000;  // get_document 默认返回的正文字符数
+
+// search_files 返回的一条结果：只带预览片段，不带全文
+#[derive(Debug, Serialize)]
+pub struct ToolSearchHit {
+    pub title: String,
+    pub path: String,
+    pub tags: Vec<String>,
+    pub score: f32,
+    pub preview: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolSearchResult {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub hits: Vec<ToolSearchHit>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetDocumentArgs {
+    #[schemars(description = "文档路径，使用 search_files 返回的写法")]
+    pub path: String,
+    #[schemars(description = "最多返回的正文字符数，默认 8000", range(min = 1))]
+    pub max_chars: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolDocument {
+    pub title: String,
+    pub path: String,
+    pub tags: Vec<String>,
+    pub timestamp: u64,
+    pub content: String,
+    pub truncated: bool, // 正文超过 max_chars 被截断
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct IndexPathArgs {
+    #[schemars(description = "文件路径")]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolIndexResult {
+    pub path: PathBuf,
+    pub title: String,
+}
+
+// 工具调用失败 (参数不对、文件不存在等)；按 MCP 约定放在 result 里以 isError 返回，不算协议错误
+struct ToolError(String);
+
+pub struct McpServer {
+    index: Index,
+    schema: Schema,
+    config: Arc<Config>,
+    bert: LazyBert, // 第一次 index_path 时才加载模型
+    events: EventBus, // stdio 上没有事件订阅者，只是满足索引函数的参数
+}
+
+impl McpServer {
+    pub fn new(index: Index, schema: Schema, config: Arc<Config>) -> Self {
+        let bert = LazyBert::new(config.ai.clone());
+        Self { index, schema, config, bert, events: EventBus::default() }
+    }
+
+    // 逐行读取请求直到输入结束，每个请求 (通知除外) 写回一行响应
+    pub fn run<R: BufRead, W: Write>(&self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    // 处理一条 JSON-RPC 消息；通知 (没有 id) 不需要回复，返回 None
+    pub fn handle_message(&self, line: &str) -> Option<Value> {
+        let Request { id, method, params } = match rpc::parse_request(line) {
+            Ok(request) => request,
+            Err(response) => return Some(response),
+        };
+
+        // 通知 (如 notifications/initialized) 不需要回复
+        let id = id?;
+        let result = match method.as_str() {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "ai_search", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            // 工具内部 panic 也只回一个错误，不让 agent 的会话断掉
+            "tools/call" => panic::catch_unwind(AssertUnwindSafe(|| self.call_tool(&params)))
+                .unwrap_or_else(|_| Err((INTERNAL_ERROR, "调用工具时发生内部错误".to_string()))),
+            other => Err((METHOD_NOT_FOUND, format!("不支持的方法: {}", other))),
+        };
+
+        Some(match result {
+            Ok(result) => success_response(id, result),
+            Err((code, message)) => error_response(id, code, message),
+        })
+    }
+
+    fn call_tool(&self, params: &Value) -> std::result::Result<Value, (i64, String)> {
+        let name = params.get("name").and_then(Value::as_str).ok_or((INVALID_PARAMS, "缺少工具名 name".to_string()))?;
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let outcome = match name {
+            "search_files" => parse_args(arguments).and_then(|args| self.search_files(args)),
+            "get_document" => parse_args(arguments).and_then(|args| self.get_document(args)),
+            "index_path" => parse_args(arguments).and_then(|args| self.index_path(args)),
+            other => return Err((INVALID_PARAMS, format!("未知工具: {}", other))),
+        };
+
+        let (text, is_error) = match outcome {
+            Ok(value) => (serde_json::to_string_pretty(&value).unwrap_or_default(), false),
+            Err(ToolError(message)) => (message, true),
+        };
+        Ok(json!({ "content": [{ "type": "text", "text": text }], "isError": is_error }))
+    }
+
+    fn search_files(&self, mut request: SearchRequest) -> std::result::Result<Value, ToolError> {
+        if request.query.trim().is_empty() {
+            return Err(ToolError("query 不能为空".to_string()));
+        }
+        request.options.limit = request.options.limit.clamp(1, MAX_SEARCH_LIMIT);
+
+        let page = search::search_page(&self.index, &request.query, &request.options).map_err(tool_error)?;
+        let result = ToolSearchResult {
+            total: page.total,
+            offset: page.offset,
+            limit: page.limit,
+            hits: page
+                .hits
+                .into_iter()
+                .map(|hit| ToolSearchHit { title: hit.title, path: hit.path, tags: hit.tags, score: hit.score, preview: hit.snippet })
+                .collect(),
+        };
+        to_value(&result)
+    }
+
+    fn get_document(&self, args: GetDocumentArgs) -> std::result::Result<Value, ToolError> {
+        // 先按原样查，查不到再换成扫描时的路径写法 (绝对路径、省略 "./" 等)
+        let mut found = search::get_document(&self.index, &args.path).map_err(tool_error)?;
+        if found.is_none() && let Some(normalized) = self.config.watch.normalize_path(Path::new(&args.path)) {
+            found = search::get_document(&self.index, &normalized.to_string_lossy()).map_err(tool_error)?;
+        }
+        let doc = found.ok_or_else(|| ToolError(format!("索引中没有这个文件: {}", args.path)))?;
+
+        let max_chars = args.max_chars.unwrap_or(DEFAULT_DOCUMENT_CHARS);
+        let truncated = doc.body.chars().count() > max_chars;
+        let content = doc.body.chars().take(max_chars).collect();
+        to_value(&ToolDocument { title: doc.title, path: doc.path, tags: doc.tags, timestamp: doc.timestamp, content, truncated })
+    }
+
+    fn index_path(&self, args: IndexPathArgs) -> std::result::Result<Value, ToolError> {
+        let path = indexer::resolve_external_path(&args.path, &self.config).map_err(|rejection| ToolError(format!("{:?}: {}", args.path, rejection)))?;
+
+        let bert = self.bert.get();
+        let mut results = indexer::index_paths(std::slice::from_ref(&path), &self.index, &self.schema, bert.as_deref(), &self.config, &self.events)
+            .map_err(tool_error)?;
+        let title = results.remove(0).map_err(tool_error)?;
+        to_value(&ToolIndexResult { path, title })
+    }
+}
+
+// tools/list 返回的工具定义，inputSchema 由参数类型 (SearchRequest 等) 生成，和实际解析的字段保持一致
+pub fn tool_definitions() -> Value {
+    let mut search_schema = input_schema::<SearchRequest>();
+    search_schema["properties"]["limit"]["maximum"] = json!(MAX_SEARCH_LIMIT);
+    json!([
+        {
+            "name": "search_files",
+            "description": "在本地文档索引中全文搜索 (支持中文分词)，返回标题、路径、标签和命中片段",
+            "inputSchema": search_schema
+        },
+        {
+            "name": "get_document",
+            "description": "按路径读取已索引文档的标题、标签和正文",
+            "inputSchema": input_schema::<GetDocumentArgs>()
+        },
+        {
+            "name": "index_path",
+            "description": "立即 (重新) 索引一个文件；只接受监控目录下、符合过滤规则的文件",
+            "inputSchema": input_schema::<IndexPathArgs>()
+        }
+    ])
+}
+
+// 参数类型的 JSON Schema，去掉 agent 用不到的 $schema 和 title
+fn input_schema<T: JsonSchema>() -> Value {
+    let mut schema = schema_for!(T).to_value();
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+        object.remove("title");
+    }
+    schema
+}
+
+// 在 stdio 上启动 MCP 服务，直到 stdin 关闭
+pub fn serve_stdio(server: &McpServer) -> Result<()> {
+    eprintln!(" [MCP] 服务已启动，等待 stdin 上的 JSON-RPC 请求");
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    server.run(stdin.lock(), stdout.lock()).map_err(|e| anyhow!("MCP 服务异常退出: {:#}", e))
+}
+
+fn parse_args<T: for<'de> Deserialize<'de>>(arguments: Value) -> std::result::Result<T, ToolError> {
+    serde_json::from_value(arguments).map_err(|e| ToolError(format!("参数不正确: {}", e)))
+}
+
+fn to_value<T: Serialize>(value: &T) -> std::result::Result<Value, ToolError> {
+    serde_json::to_value(value).map_err(|e| ToolError(format!("结果无法序列化: {}", e)))
+}
+
+fn tool_error(e: anyhow::Error) -> ToolError {
+    ToolError(format!("{:#}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MIN_WRITER_HEAP_SIZE, RootConfig};
+    use crate::indexer::init_ram_index;
+
+    // 监控目录下放两个文件，AI 关闭，索引在内存里
+    fn test_server(dir: &Path) -> McpServer {
+        std::fs::write(dir.join("rust.txt"), "Rust 所有权与借用").unwrap();
+        std::fs::write(dir.join("notes.md"), "神经网络 学习笔记").unwrap();
+        let mut config = Config::default();
+        config.ai.enabled = false;
+        config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+        config.watch.roots = vec![RootConfig::new(dir)];
+        let (index, schema) = init_ram_index();
+        McpServer::new(index, schema, Arc::new(config))
+    }
+
+    // 把多行请求喂给 run()，返回逐行解析后的响应
+    fn run_lines(server: &McpServer, requests: &[Value]) -> Vec<Value> {
+        let input: String = requests.iter().map(|request| format!("{}\n", request)).collect();
+        let mut output = Vec::new();
+        server.run(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    fn call(id: u64, name: &str, arguments: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": { "name": name, "arguments": arguments } })
+    }
+
+    // 工具结果里的 text 是 JSON，解析出来方便断言
+    fn tool_output(response: &Value) -> Value {
+        assert_eq!(response["result"]["isError"], false, "{}", response);
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn session_initializes_lists_tools_and_searches() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let responses = run_lines(
+            &server,
+            &[
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+                json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+                call(3, "index_path", json!({ "path": dir.path().join("rust.txt") })),
+                call(4, "index_path", json!({ "path": dir.path().join("notes.md") })),
+                call(5, "search_files", json!({ "query": "所有权 OR 神经网络", "limit": 1000, "offset": 1 })),
+            ],
+        );
+
+        // 通知没有回复，其余请求按顺序各回一行
+        let ids: Vec<_> = responses.iter().map(|response| response["id"].clone()).collect();
+        assert_eq!(ids, [json!(1), json!(2), json!(3), json!(4), json!(5)]);
+        assert_eq!(responses[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+        let tools: Vec<_> = responses[1]["result"]["tools"].as_array().unwrap().iter().map(|tool| tool["name"].clone()).collect();
+        assert_eq!(tools, [json!("search_files"), json!("get_document"), json!("index_path")]);
+        assert_eq!(tool_output(&responses[2])["title"], "rust");
+
+        // limit 超出上限按 MAX_SEARCH_LIMIT 处理
+        let result = tool_output(&responses[4]);
+        assert_eq!((result["total"].clone(), result["offset"].clone(), result["limit"].clone()), (json!(2), json!(1), json!(MAX_SEARCH_LIMIT)));
+        assert_eq!(result["hits"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tool_failures_are_results_and_protocol_errors_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let responses = run_lines(
+            &server,
+            &[
+                call(1, "search_files", json!({ "query": "  " })),
+                call(2, "index_path", json!({ "path": dir.path().join("missing.txt") })),
+                call(3, "no_such_tool", json!({})),
+                json!({ "jsonrpc": "2.0", "id": 4, "method": "resources/list" }),
+            ],
+        );
+
+        assert_eq!(responses[0]["result"]["isError"], true);
+        assert_eq!(responses[1]["result"]["isError"], true);
+        assert_eq!(responses[2]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    // 声明的参数和实际解析的字段要对得上：required 的参数少一个就解析失败，
+    // 每个声明的参数传错类型都必须报错 (没有对应字段的键会被 serde 直接忽略)
+    fn check_schema<T: for<'de> Deserialize<'de>>(name: &str, expected: &[&str], minimal: Value) {
+        let tools = tool_definitions();
+        let tool = tools.as_array().unwrap().iter().find(|tool| tool["name"] == name).unwrap();
+        let schema = &tool["inputSchema"];
+        let properties: Vec<&String> = schema["properties"].as_object().unwrap().keys().collect();
+        assert_eq!(properties, expected, "{}", name);
+        let required: Vec<&Value> = schema["required"].as_array().unwrap().iter().collect();
+        let given: Vec<Value> = minimal.as_object().unwrap().keys().map(|key| json!(key)).collect();
+        assert_eq!(required, given.iter().collect::<Vec<_>>(), "{}", name);
+
+        assert!(parse_args::<T>(minimal.clone()).is_ok(), "{}", name);
+        for key in &given {
+            let mut args = minimal.clone();
+            args.as_object_mut().unwrap().remove(key.as_str().unwrap());
+            assert!(parse_args::<T>(args).is_err(), "{} 缺少 {}", name, key);
+        }
+        for property in properties {
+            let mut args = minimal.clone();
+            args[property] = json!({ "wrong": "type" });
+            assert!(parse_args::<T>(args).is_err(), "{}.{} 没有被解析", name, property);
+        }
+    }
+
+    #[test]
+    fn advertised_schemas_match_the_argument_types() {
+        check_schema::<SearchRequest>("search_files", &["file_type", "limit", "offset", "query"], json!({ "query": "rust" }));
+        check_schema::<GetDocumentArgs>("get_document", &["max_chars", "path"], json!({ "path": "a.txt" }));
+        check_schema::<IndexPathArgs>("index_path", &["path"], json!({ "path": "a.txt" }));
+
+        let tools = tool_definitions();
+        assert_eq!(tools[0]["inputSchema"]["properties"]["limit"]["maximum"], MAX_SEARCH_LIMIT);
+        assert_eq!(tools[0]["inputSchema"]["properties"]["limit"]["default"], search::SearchOptions::default().limit);
+    }
+}
@@ -4,4 +4,13 @@ pub struct FileDoc {
     pub title: String,
     pub content: String,
     pub path: String,
-}
\ No newline at end of file
+}
+// 索引里存着的一条文档记录 (tags 已按空格拆开)
+#[derive(Debug)]
+pub struct StoredDoc {
+    pub title: String,
+    pub path: String,
+    pub tags: Vec<String>,
+    pub timestamp: u64,
+    pub body: String,
+}
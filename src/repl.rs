@@ -0,0 +1,299 @@
+// repl.rs
+// 交互模式的命令解析与分页状态 (不涉及输入输出，方便单独测试)
+use crate::search::{MAX_PAGE_LIMIT, SearchOptions};
+
+pub const DEFAULT_PAGE_SIZE: usize = 5;
+
+#[derive(Debug, PartialEq)]
+pub enum ReplCommand {
+    Empty,
+    Quit,
+    NextPage,          // n
+    PrevPage,          // p
+    Page(usize),       // page 4 (从 1 开始)
+    Limit(usize),      // limit 50
+    FileType(Option<String>), // type pdf 只看某种扩展名，type all 取消过滤
+    Reload,            // reload: 重新读取配置文件
+    Search(String),    // 其他输入都当作搜索词
+    Invalid(String),   // 命令格式不对时给用户的提示
+}
+
+pub fn parse_command(input: &str) -> ReplCommand {
+    let input = input.trim();
+    let mut parts = input.split_whitespace();
+    let (Some(head), arg, extra) = (parts.next(), parts.next(), parts.next()) else {
+        return ReplCommand::Empty;
+    };
+
+    match (head, arg, extra) {
+        ("quit" | "exit", None, None) => ReplCommand::Quit,
+        ("n", None, None) => ReplCommand::NextPage,
+        ("p", None, None) => ReplCommand::PrevPage,
+        ("reload", None, None) => ReplCommand::Reload,
+        ("page", Some(n), None) => match n.parse::<usize>() {
+            Ok(n) if n >= 1 => ReplCommand::Page(n),
+            _ => ReplCommand::Invalid(format!("页码需要是正整数: {}", n)),
+        },
+        ("limit", Some(n), None) => match n.parse::<usize>() {
+            Ok(n) if (1..=MAX_PAGE_LIMIT).contains(&n) => ReplCommand::Limit(n),
+            _ => ReplCommand::Invalid(format!("每页条数需要是 1 到 {} 之间的整数: {}", MAX_PAGE_LIMIT, n)),
+        },
+        ("type", Some("all"), None) => ReplCommand::FileType(None),
+        ("type", Some(ext), None) => match ext.trim_start_matches('.') {
+            "" => ReplCommand::Invalid(format!("扩展名不能为空: {}", ext)),
+            ext => ReplCommand::FileType(Some(ext.to_string())),
+        },
+        _ => ReplCommand::Search(input.to_string()),
+    }
+}
+
+// 一条命令执行后主循环要做的事
+#[derive(Debug, PartialEq)]
+pub enum ReplStep {
+    Nothing,
+    Quit,
+    Message(String),
+    Reload,
+    Run { query: String, options: SearchOptions },
+}
+
+// 记住上一次的搜索，翻页和改每页条数时用同样的查询重新搜索
+#[derive(Debug)]
+pub struct ReplState {
+    pub last_query: Option<String>,
+    pub page: usize, // 从 0 开始
+    pub limit: usize,
+    pub total: usize, // 上一次搜索的命中总数
+    pub file_type: Option<String>, // type 命令设置的扩展名过滤，换查询时保留
+}
+
+impl Default for ReplState {
+    fn default() -> Self {
+        Self { last_query: None, page: 0, limit: DEFAULT_PAGE_SIZE, total: 0, file_type: None }
+    }
+}
+
+impl ReplState {
+    pub fn total_pages(&self) -> usize {
+        self.total.div_ceil(self.limit).max(1)
+    }
+
+    pub fn apply(&mut self, command: ReplCommand) -> ReplStep {
+        match command {
+            ReplCommand::Empty => ReplStep::Nothing,
+            ReplCommand::Quit => ReplStep::Quit,
+            ReplCommand::Reload => ReplStep::Reload,
+            ReplCommand::Invalid(message) => ReplStep::Message(message),
+            ReplCommand::Search(query) => {
+                // 换了查询就从第一页开始
+                self.last_query = Some(query);
+                self.page = 0;
+                self.total = 0;
+                self.run()
+            }
+            ReplCommand::NextPage => self.goto_page(self.page + 1),
+            ReplCommand::PrevPage => self.goto_page(self.page.saturating_sub(1)),
+            ReplCommand::Page(n) => self.goto_page(n - 1),
+            ReplCommand::Limit(limit) => {
+                // 尽量保持当前页第一条结果还在新的一页里
+                let first_hit = self.page * self.limit;
+                self.limit = limit;
+                self.page = first_hit / limit;
+                if self.last_query.is_none() {
+                    return ReplStep::Message(format!("每页显示 {} 条", limit));
+                }
+                self.goto_page(self.page)
+            }
+            ReplCommand::FileType(file_type) => {
+                // 过滤条件变了，结果集也变了，回到第一页
+                self.file_type = file_type;
+                self.page = 0;
+                self.total = 0;
+                match (&self.last_query, &self.file_type) {
+                    (Some(_), _) => self.run(),
+                    (None, Some(ext)) => ReplStep::Message(format!("只看 .{} 文件", ext)),
+                    (None, None) => ReplStep::Message("不再按文件类型过滤".to_string()),
+                }
+            }
+        }
+    }
+
+    // 搜索完成后记录总数；如果当前页已经超出末尾 (比如期间文件被删了)，返回末页的重新搜索
+    pub fn record_total(&mut self, total: usize) -> ReplStep {
+        self.total = total;
+        let last_page = self.total_pages() - 1;
+        if self.page > last_page {
+            self.page = last_page;
+            return self.run();
+        }
+        ReplStep::Nothing
+    }
+
+    pub fn page_summary(&self) -> String {
+        match &self.file_type {
+            Some(ext) => format!("第 {}/{} 页 (共 {} 条，只看 .{} 文件)", self.page + 1, self.total_pages(), self.total, ext),
+            None => format!("第 {}/{} 页 (共 {} 条)", self.page + 1, self.total_pages(), self.total),
+        }
+    }
+
+    fn goto_page(&mut self, page: usize) -> ReplStep {
+        if self.last_query.is_none() {
+            return ReplStep::Message("还没有搜索过，先输入关键词".to_string());
+        }
+        // 翻过末尾时停在最后一页
+        self.page = page.min(self.total_pages() - 1);
+        self.run()
+    }
+
+    fn run(&self) -> ReplStep {
+        match &self.last_query {
+            Some(query) => ReplStep::Run {
+                query: query.clone(),
+                options: SearchOptions { offset: self.page * self.limit, limit: self.limit, file_type: self.file_type.clone() },
+            },
+            None => ReplStep::Nothing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 执行一条输入，返回要跑的搜索 (offset, limit)
+    fn run(state: &mut ReplState, input: &str) -> (usize, usize) {
+        match state.apply(parse_command(input)) {
+            ReplStep::Run { options, .. } => (options.offset, options.limit),
+            other => panic!("{:?} 没有触发搜索: {:?}", input, other),
+        }
+    }
+
+    // 搜索 query 并记下命中总数
+    fn searched(query: &str, total: usize) -> ReplState {
+        let mut state = ReplState::default();
+        assert_eq!(run(&mut state, query), (0, DEFAULT_PAGE_SIZE));
+        assert_eq!(state.record_total(total), ReplStep::Nothing);
+        state
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse_command("   "), ReplCommand::Empty);
+        assert_eq!(parse_command("quit"), ReplCommand::Quit);
+        assert_eq!(parse_command(" n "), ReplCommand::NextPage);
+        assert_eq!(parse_command("p"), ReplCommand::PrevPage);
+        assert_eq!(parse_command("page 4"), ReplCommand::Page(4));
+        assert_eq!(parse_command("limit 50"), ReplCommand::Limit(50));
+        assert_eq!(parse_command("reload"), ReplCommand::Reload);
+        assert_eq!(parse_command("type pdf"), ReplCommand::FileType(Some("pdf".to_string())));
+        assert_eq!(parse_command("type .md"), ReplCommand::FileType(Some("md".to_string())));
+        assert_eq!(parse_command("type all"), ReplCommand::FileType(None));
+        // 不是命令格式的输入都当作搜索词
+        assert_eq!(parse_command("n 神经网络"), ReplCommand::Search("n 神经网络".to_string()));
+        assert_eq!(parse_command("page"), ReplCommand::Search("page".to_string()));
+        assert!(matches!(parse_command("page 0"), ReplCommand::Invalid(_)));
+        assert!(matches!(parse_command("limit ten"), ReplCommand::Invalid(_)));
+        assert!(matches!(parse_command("type ."), ReplCommand::Invalid(_)));
+    }
+
+    #[test]
+    fn limit_is_capped() {
+        assert_eq!(parse_command(&format!("limit {}", MAX_PAGE_LIMIT)), ReplCommand::Limit(MAX_PAGE_LIMIT));
+        for n in ["1001", "1000000000000", "99999999999999999999999"] {
+            assert!(matches!(parse_command(&format!("limit {}", n)), ReplCommand::Invalid(_)), "{}", n);
+        }
+    }
+
+    #[test]
+    fn next_prev_and_page_commands() {
+        let mut state = searched("rust", 23);
+        assert_eq!(state.page_summary(), "第 1/5 页 (共 23 条)");
+
+        assert_eq!(run(&mut state, "n"), (5, 5));
+        assert_eq!(run(&mut state, "n"), (10, 5));
+        assert_eq!(run(&mut state, "p"), (5, 5));
+        assert_eq!(run(&mut state, "page 5"), (20, 5));
+        // 翻过末尾停在最后一页，第一页再往前还是第一页
+        assert_eq!(run(&mut state, "n"), (20, 5));
+        assert_eq!(run(&mut state, "page 99"), (20, 5));
+        assert_eq!(run(&mut state, "page 1"), (0, 5));
+        assert_eq!(run(&mut state, "p"), (0, 5));
+    }
+
+    #[test]
+    fn paging_before_any_search_only_prints_a_hint() {
+        let mut state = ReplState::default();
+        assert!(matches!(state.apply(ReplCommand::NextPage), ReplStep::Message(_)));
+        assert!(matches!(state.apply(ReplCommand::Page(2)), ReplStep::Message(_)));
+        assert_eq!(state.apply(ReplCommand::Limit(20)), ReplStep::Message("每页显示 20 条".to_string()));
+        assert_eq!(run(&mut state, "rust"), (0, 20));
+    }
+
+    #[test]
+    fn page_past_the_end_lands_on_the_last_page() {
+        let mut state = searched("rust", 23);
+        assert_eq!(run(&mut state, "page 5"), (20, 5));
+
+        // 文件被删，总数变少：重新搜索最后一页
+        let step = state.record_total(8);
+        assert_eq!(step, ReplStep::Run { query: "rust".to_string(), options: SearchOptions { offset: 5, limit: 5, file_type: None } });
+        assert_eq!(state.record_total(8), ReplStep::Nothing);
+        assert_eq!(state.page_summary(), "第 2/2 页 (共 8 条)");
+
+        // 没有结果时停在第一页
+        assert!(matches!(state.record_total(0), ReplStep::Run { .. }));
+        assert_eq!(state.page_summary(), "第 1/1 页 (共 0 条)");
+    }
+
+    #[test]
+    fn new_query_resets_pagination() {
+        let mut state = searched("rust", 23);
+        run(&mut state, "page 3");
+        run(&mut state, "limit 4");
+
+        match state.apply(parse_command("神经网络")) {
+            ReplStep::Run { query, options } => {
+                assert_eq!(query, "神经网络");
+                assert_eq!((options.offset, options.limit), (0, 4), "换查询回到第一页，每页条数保留");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(state.total, 0);
+    }
+
+    #[test]
+    fn limit_change_keeps_the_first_hit_visible() {
+        let mut state = searched("rust", 100);
+        // 第 4 页第一条是第 15 条 (offset 15)
+        assert_eq!(run(&mut state, "page 4"), (15, 5));
+
+        let (offset, limit) = run(&mut state, "limit 10");
+        assert_eq!((offset, limit), (10, 10));
+        assert!((offset..offset + limit).contains(&15));
+
+        let (offset, limit) = run(&mut state, "limit 3");
+        assert_eq!((offset, limit), (9, 3));
+        assert!((offset..offset + limit).contains(&10));
+    }
+
+    #[test]
+    fn type_filter_applies_to_later_searches_and_resets_the_page() {
+        let mut state = ReplState::default();
+        assert_eq!(state.apply(parse_command("type pdf")), ReplStep::Message("只看 .pdf 文件".to_string()));
+
+        let file_type = |step: ReplStep| match step {
+            ReplStep::Run { options, .. } => (options.offset, options.file_type),
+            other => panic!("没有触发搜索: {:?}", other),
+        };
+        assert_eq!(file_type(state.apply(parse_command("rust"))), (0, Some("pdf".to_string())));
+        state.record_total(23);
+        assert_eq!(state.page_summary(), "第 1/5 页 (共 23 条，只看 .pdf 文件)");
+
+        // 翻页沿用过滤条件；改过滤条件时重新搜索第一页
+        assert_eq!(file_type(state.apply(parse_command("page 3"))), (10, Some("pdf".to_string())));
+        assert_eq!(file_type(state.apply(parse_command("type md"))), (0, Some("md".to_string())));
+        state.record_total(23);
+        assert_eq!(file_type(state.apply(parse_command("n"))), (5, Some("md".to_string())));
+        assert_eq!(file_type(state.apply(parse_command("type all"))), (0, None));
+    }
+}
@@ -0,0 +1,298 @@
+// rpc.rs
+// 给编辑器插件用的 JSON-RPC 2.0 接口 (ai_search rpc)，走 stdin/stdout。
+// 分帧方式：每行一个 JSON 对象，请求和响应里都不能有换行 (serde_json 默认输出正好是单行)。
+//
+//   方法          params            result
+//   search        SearchRequest     SearchResponse
+//   index_file    IndexRequest      IndexResult
+//   delete_file   DeleteRequest     DeleteResponse
+//   stats         (不需要)          StatsResponse
+//   suggest       SuggestRequest    [Suggestion]
+//   shutdown      (不需要)          null，回复之后退出
+//
+// 没有 id 的通知不回复；JSON 格式错误、未知方法、参数不对都返回 error 响应，进程不会退出。
+// stdout 只输出协议消息，日志都走 stderr
+use std::io::{BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tantivy::Index;
+use tantivy::schema::Schema;
+
+use crate::ai::LazyBert;
+use crate::config::Config;
+use crate::events::EventBus;
+use crate::api::{ApiErrorKind, DeleteRequest, DeleteResponse, IndexRequest, IndexResult, SearchRequest, StatsResponse, SuggestRequest, Suggestion};
+use crate::indexer;
+use crate::search;
+
+// JSON-RPC 规定的错误码
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+// 方法执行失败 (文件被拒绝、索引出错等)，message 里是具体原因
+pub const SERVER_ERROR: i64 = -32000;
+
+// 一条已经解析的请求；id 为 None 表示通知
+pub struct Request {
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Value,
+}
+
+// 解析一行 JSON-RPC 消息，格式不对时直接返回要回复的错误响应 (MCP 服务也用它)
+pub fn parse_request(line: &str) -> std::result::Result<Request, Value> {
+    let message: Value = serde_json::from_str(line)
+        .map_err(|e| error_response(Value::Null, PARSE_ERROR, format!("JSON 解析失败: {}", e)))?;
+
+    let id = message.get("id").cloned();
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        return Err(error_response(id.unwrap_or(Value::Null), INVALID_REQUEST, "缺少 method"));
+    };
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    Ok(Request { id, method: method.to_string(), params })
+}
+
+pub fn success_response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn error_response(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message.into() } })
+}
+
+pub struct RpcServer {
+    index: Index,
+    schema: Schema,
+    config: Arc<Config>,
+    bert: LazyBert, // 第一次 index_file 时才加载模型
+    events: EventBus, // stdio 上没有事件订阅者，只是满足索引函数的参数
+    shutdown: AtomicBool,
+}
+
+impl RpcServer {
+    pub fn new(index: Index, schema: Schema, config: Arc<Config>) -> Self {
+        let bert = LazyBert::new(config.ai.clone());
+        Self { index, schema, config, bert, events: EventBus::default(), shutdown: AtomicBool::new(false) }
+    }
+
+    // 收到过 shutdown 请求
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    // 逐行处理请求，直到 stdin 关闭或收到 shutdown
+    pub fn run<R: BufRead, W: Write>(&self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+            if self.is_shutdown() {
+                break;
+            }
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    // 处理一行消息，返回要写回的响应；通知返回 None
+    pub fn handle_message(&self, line: &str) -> Option<Value> {
+        let request = match parse_request(line) {
+            Ok(request) => request,
+            Err(response) => return Some(response),
+        };
+
+        // 方法内部 panic 也只回一个错误，不让整个进程退出
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.dispatch(&request.method, request.params)))
+            .unwrap_or_else(|_| Err((INTERNAL_ERROR, format!("处理 {} 时发生内部错误", request.method))));
+
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => success_response(id, result),
+            Err((code, message)) => error_response(id, code, message),
+        })
+    }
+
+    fn dispatch(&self, method: &str, params: Value) -> std::result::Result<Value, (i64, String)> {
+        match method {
+            "search" => {
+                let request: SearchRequest = parse_params(params)?;
+                if request.query.trim().is_empty() {
+                    return Err((INVALID_PARAMS, "query 不能为空".to_string()));
+                }
+                let page = search::search_page(&self.index, &request.query, &request.options).map_err(server_error)?;
+                to_value(&page)
+            }
+            "index_file" => {
+                let request: IndexRequest = parse_params(params)?;
+                let path = indexer::resolve_external_path(&request.path, &self.config)
+                    .map_err(|rejection| (SERVER_ERROR, format!("{:?}: {}", request.path, rejection)))?;
+                let bert = self.bert.get();
+                let mut results = indexer::index_paths(std::slice::from_ref(&path), &self.index, &self.schema, bert.as_deref(), &self.config, &self.events)
+                    .map_err(server_error)?;
+                let title = results.remove(0).map_err(server_error)?;
+                to_value(&IndexResult { path, title: Some(title), error: None })
+            }
+            "delete_file" => {
+                let request: DeleteRequest = parse_params(params)?;
+                let deleted = indexer::delete_document(&self.index, &self.schema, &request.path, &self.config, &self.events).map_err(server_error)?;
+                to_value(&DeleteResponse { deleted })
+            }
+            "stats" => to_value(&StatsResponse::collect(&self.index, self.bert.enabled(), &self.config).map_err(server_error)?),
+            "suggest" => {
+                let request: SuggestRequest = parse_params(params)?;
+                let tags = search::list_tags(&self.index, &request.prefix, request.limit).map_err(server_error)?;
+                let suggestions: Vec<Suggestion> = tags.into_iter().map(|(tag, count)| Suggestion { tag, count }).collect();
+                to_value(&suggestions)
+            }
+            "shutdown" => {
+                self.shutdown.store(true, Ordering::SeqCst);
+                Ok(Value::Null)
+            }
+            other => Err((METHOD_NOT_FOUND, format!("不支持的方法: {}", other))),
+        }
+    }
+}
+
+// 在 stdio 上启动 JSON-RPC 服务
+pub fn serve_stdio(server: &RpcServer) -> Result<()> {
+    eprintln!(" [RPC] 服务已启动，每行一个 JSON-RPC 请求");
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    server.run(stdin.lock(), stdout.lock())
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> std::result::Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, format!("参数不正确: {}", e)))
+}
+
+fn to_value<T: Serialize>(value: &T) -> std::result::Result<Value, (i64, String)> {
+    serde_json::to_value(value).map_err(|e| (INTERNAL_ERROR, format!("结果无法序列化: {}", e)))
+}
+
+// 查询语法错误等调用方的问题按参数错误返回，其余都是方法执行失败
+fn server_error(e: anyhow::Error) -> (i64, String) {
+    let code = match ApiErrorKind::classify(&e) {
+        ApiErrorKind::InvalidRequest => INVALID_PARAMS,
+        _ => SERVER_ERROR,
+    };
+    (code, format!("{:#}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MIN_WRITER_HEAP_SIZE, RootConfig};
+    use crate::indexer::init_ram_index;
+
+    // 监控目录下放一个文件，AI 关闭，索引在内存里
+    fn test_server(dir: &std::path::Path) -> RpcServer {
+        std::fs::write(dir.join("rust.txt"), "Rust 所有权与借用").unwrap();
+        let mut config = Config::default();
+        config.ai.enabled = false;
+        config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+        config.watch.roots = vec![RootConfig::new(dir)];
+        let (index, schema) = init_ram_index();
+        RpcServer::new(index, schema, Arc::new(config))
+    }
+
+    // 把原样的多行输入喂给 run()，返回逐行解析后的响应
+    fn run_lines(server: &RpcServer, lines: &[&str]) -> Vec<Value> {
+        let input: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        let mut output = Vec::new();
+        server.run(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    fn error_code(response: &Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap_or_else(|| panic!("不是错误响应: {}", response))
+    }
+
+    #[test]
+    fn protocol_errors_get_standard_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let responses = run_lines(
+            &server,
+            &[
+                "{not json",
+                r#"{"jsonrpc":"2.0","id":1}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"no_such_method"}"#,
+                r#"{"jsonrpc":"2.0","id":3,"method":"search","params":{"limit":5}}"#,
+                r#"{"jsonrpc":"2.0","id":4,"method":"search","params":{"query":"nosuchfield:rust"}}"#,
+                r#"{"jsonrpc":"2.0","id":5,"method":"search","params":{"query":"  "}}"#,
+            ],
+        );
+
+        let codes: Vec<_> = responses.iter().map(error_code).collect();
+        assert_eq!(codes, [PARSE_ERROR, INVALID_REQUEST, METHOD_NOT_FOUND, INVALID_PARAMS, INVALID_PARAMS, INVALID_PARAMS]);
+        // 解析不了的消息拿不到 id，按规范回 null
+        assert_eq!(responses[0]["id"], Value::Null);
+        assert_eq!(responses[1]["id"], 1);
+    }
+
+    #[test]
+    fn notifications_get_no_reply_and_shutdown_stops_the_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let path = dir.path().join("rust.txt");
+        let index = json!({ "jsonrpc": "2.0", "method": "index_file", "params": { "path": path } }).to_string();
+        let responses = run_lines(
+            &server,
+            &[
+                &index, // 通知：照样执行，只是不回复
+                r#"{"jsonrpc":"2.0","id":1,"method":"search","params":{"query":"所有权"}}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#,
+                r#"{"jsonrpc":"2.0","id":3,"method":"stats"}"#,
+            ],
+        );
+
+        assert_eq!(responses.len(), 2, "{:?}", responses);
+        assert_eq!(responses[0]["result"]["total"], 1);
+        assert_eq!(responses[0]["result"]["hits"][0]["path"], json!(path));
+        assert_eq!(responses[1], success_response(json!(2), Value::Null));
+        assert!(server.is_shutdown());
+    }
+
+    #[test]
+    fn suggest_delete_file_and_stats_round_trip() {
+        let mut config = Config::default();
+        config.ai.enabled = false;
+        config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+        let server = RpcServer::new(crate::search::tests::tagged_index(), indexer::build_schema(), Arc::new(config));
+        let responses = run_lines(
+            &server,
+            &[
+                r#"{"jsonrpc":"2.0","id":1,"method":"suggest","params":{"prefix":"机器","limit":1}}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"suggest","params":{}}"#,
+                r#"{"jsonrpc":"2.0","id":3,"method":"delete_file","params":{"path":"./docs/a.txt"}}"#,
+                r#"{"jsonrpc":"2.0","id":4,"method":"delete_file","params":{"path":"./docs/missing.txt"}}"#,
+                r#"{"jsonrpc":"2.0","id":5,"method":"delete_file","params":{}}"#,
+                r#"{"jsonrpc":"2.0","id":6,"method":"stats"}"#,
+                r#"{"jsonrpc":"2.0","id":7,"method":"suggest","params":{"prefix":"神经"}}"#,
+            ],
+        );
+
+        assert_eq!(responses[0]["result"], json!([{ "tag": "机器学习", "count": 3 }]));
+        assert_eq!(responses[1]["result"].as_array().unwrap().len(), 4);
+        assert_eq!(responses[2]["result"], json!({ "deleted": true }));
+        // 索引里没有的路径不算错误，只是 deleted 为 false
+        assert_eq!(responses[3]["result"], json!({ "deleted": false }));
+        assert_eq!(error_code(&responses[4]), INVALID_PARAMS);
+        assert_eq!(responses[5]["result"], json!({ "num_docs": 3, "num_segments": 1, "ai_enabled": false, "watch_roots": ["./docs"] }));
+        // a.txt 是唯一带 "神经网络" 的文档，删掉之后标签也跟着消失
+        assert_eq!(responses[6]["result"], json!([]));
+    }
+}
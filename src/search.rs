@@ -1,48 +1,313 @@
 // search.rs
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-use tantivy::{Index, TantivyDocument};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use tantivy::collector::{Count, DocSetCollector, TopDocs};
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, RegexQuery, TermQuery};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{Index, TantivyDocument, Term};
 use tantivy::schema::*;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-// 这个函数现在只负责搜索，不负责建索引
-pub fn search_index(index: &Index, query_str: &str) -> Result<()> {
+use crate::extract::format_content_preview;
+use crate::models::StoredDoc;
+
+pub const MAX_PAGE_LIMIT: usize = 1000;       // 一页最多返回的结果数
+pub const MAX_PAGE_OFFSET: usize = 100_000;   // 最多跳过的结果数，再往后翻没有意义
+
+// 分页与过滤参数
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[cfg_attr(feature = "mcp", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct SearchOptions {
+    #[cfg_attr(feature = "mcp", schemars(description = "跳过前多少条结果，用于翻页"))]
+    pub offset: usize,
+    #[cfg_attr(feature = "mcp", schemars(description = "返回多少条结果", range(min = 1)))]
+    pub limit: usize,
+    #[cfg_attr(feature = "mcp", schemars(description = "只搜某种扩展名，如 pdf、md"))]
+    pub file_type: Option<String>, // 只看某种扩展名，如 "pdf"
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self { offset: 0, limit: 5, file_type: None }
+    }
+}
+
+impl SearchOptions {
+    // 分页参数可能直接来自外部请求：limit 太大时 TopDocs 会按它预先分配内存，
+    // offset + limit 还可能溢出，所以搜索前统一限制在安全范围内
+    pub fn clamped(&self) -> Self {
+        Self {
+            offset: self.offset.min(MAX_PAGE_OFFSET),
+            limit: self.limit.clamp(1, MAX_PAGE_LIMIT),
+            file_type: self.file_type.clone(),
+        }
+    }
+}
+
+// 一条搜索结果；highlights 是 snippet 中命中词的字节范围
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub title: String,
+    pub path: String,
+    pub tags: Vec<String>,
+    pub score: f32,
+    pub snippet: String,
+    pub highlights: Vec<Range<usize>>,
+}
+
+// 一页搜索结果，total 是全部命中数 (不受分页影响)
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchPage {
+    pub hits: Vec<SearchHit>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+// 查询语句本身写错了 (括号不配对、字段名不存在等)，是调用方的问题；
+// 接口层据此返回 400 / INVALID_ARGUMENT / -32602，而不是服务端错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryParseError(pub String);
+
+impl std::fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "查询语法错误: {}", self.0)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+// 统计标签词表：每个标签出现在多少个文档里，按文档数从多到少排序
+// prefix 为空字符串时不过滤，limit 为 0 时不截断
+pub fn list_tags(index: &Index, prefix: &str, limit: usize) -> Result<Vec<(String, usize)>> {
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+    let tags_field = index.schema().get_field("tags").unwrap();
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for doc_address in searcher.search(&AllQuery, &DocSetCollector)? {
+        let doc: TantivyDocument = searcher.doc(doc_address)?;
+        let tags_str = doc.get_first(tags_field).and_then(|v| v.as_str()).unwrap_or("");
+
+        // 同一文档里重复的标签只算一次
+        let unique: HashSet<&str> = tags_str.split_whitespace().collect();
+        for tag in unique {
+            if tag.starts_with(prefix) {
+                *counts.entry(tag.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut tags: Vec<(String, usize)> = counts.into_iter().collect();
+    // 文档数相同时按标签名排序，保证输出稳定
+    tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if limit > 0 {
+        tags.truncate(limit);
+    }
+    Ok(tags)
+}
+
+// 按路径取出索引里存的文档，找不到返回 None
+pub fn get_document(index: &Index, path: &str) -> Result<Option<StoredDoc>> {
     let reader = index.reader()?;
     let searcher = reader.searcher();
-    
-    // 获取 Schema 用于字段解析
     let schema = index.schema();
     let title_field = schema.get_field("title").unwrap();
     let body_field = schema.get_field("body").unwrap();
     let path_field = schema.get_field("path").unwrap();
+    let tags_field = schema.get_field("tags").unwrap();
+    let timestamp_field = schema.get_field("timestamp").unwrap();
 
-    let query_parser = QueryParser::for_index(index, vec![title_field, body_field]);
-    
-    // 解析查询
-    let query = match query_parser.parse_query(query_str) {
-        Ok(q) => q,
-        Err(_) => {
-            println!("   查询语法错误，请重试 (例如: 'Rust AND Linux')");
-            return Ok(());
+    let term_query = TermQuery::new(Term::from_field_text(path_field, path), IndexRecordOption::Basic);
+    let top_docs = searcher.search(&term_query, &TopDocs::with_limit(1))?;
+    let Some((_score, doc_address)) = top_docs.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let doc: TantivyDocument = searcher.doc(doc_address)?;
+    Ok(Some(StoredDoc {
+        title: doc.get_first(title_field).and_then(|v| v.as_str()).unwrap_or("无标题").to_string(),
+        path: path.to_string(),
+        tags: doc.get_first(tags_field)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .split_whitespace()
+            .map(|t| t.to_string())
+            .collect(),
+        timestamp: doc.get_first(timestamp_field).and_then(|v| v.as_u64()).unwrap_or(0),
+        body: doc.get_first(body_field).and_then(|v| v.as_str()).unwrap_or("").to_string(),
+    }))
+}
+
+
+// 返回结构化的一页结果，供 TUI 等需要自己渲染的地方使用；
+// 超出范围的分页参数会被修正，返回的 offset / limit 是实际使用的值
+pub fn search_page(index: &Index, query_str: &str, options: &SearchOptions) -> Result<SearchPage> {
+    let options = &options.clamped();
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+    let schema = index.schema();
+    let title_field = schema.get_field("title").unwrap();
+    let body_field = schema.get_field("body").unwrap();
+    let path_field = schema.get_field("path").unwrap();
+    let tags_field = schema.get_field("tags").unwrap();
+
+    let query_parser = QueryParser::for_index(index, vec![title_field, body_field, tags_field]);
+    let text_query = query_parser
+        .parse_query(query_str)
+        .map_err(|e| QueryParseError(e.to_string()))?;
+
+    // path 是不分词的 STRING 字段，可以直接用正则按扩展名过滤
+    let query: Box<dyn Query> = match &options.file_type {
+        Some(ext) => {
+            let pattern = format!(".*\\.{}", regex_escape(ext));
+            let type_query = RegexQuery::from_pattern(&pattern, path_field)?;
+            Box::new(BooleanQuery::new(vec![
+                (Occur::Must, text_query),
+                (Occur::Must, Box::new(type_query)),
+            ]))
         }
+        None => text_query,
     };
 
-    let top_docs = searcher.search(&query, &TopDocs::with_limit(5))?;
+    let top_collector = TopDocs::with_limit(options.limit).and_offset(options.offset);
+    let (top_docs, total) = searcher.search(&query, &(top_collector, Count))?;
 
-    if top_docs.is_empty() {
+    let snippet_generator = SnippetGenerator::create(&searcher, &*query, body_field)?;
+
+    let mut hits = Vec::with_capacity(top_docs.len());
+    for (score, doc_address) in top_docs {
+        let doc: TantivyDocument = searcher.doc(doc_address)?;
+        let text = |field| doc.get_first(field).and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+        // 没有命中正文时 (比如只命中了标题)，退回到正文开头的预览
+        let snippet = snippet_generator.snippet_from_doc(&doc);
+        let (snippet, highlights) = if snippet.is_empty() {
+            (format_content_preview(&text(body_field)), Vec::new())
+        } else {
+            (snippet.fragment().to_string(), snippet.highlighted().to_vec())
+        };
+
+        hits.push(SearchHit {
+            title: text(title_field),
+            path: text(path_field),
+            tags: text(tags_field).split_whitespace().map(|t| t.to_string()).collect(),
+            score,
+            snippet,
+            highlights,
+        });
+    }
+
+    Ok(SearchPage { hits, total, offset: options.offset, limit: options.limit })
+}
+
+fn regex_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if !c.is_alphanumeric() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// 按交互模式的格式打印一页结果
+pub fn print_search_page(page: &SearchPage) {
+    if page.hits.is_empty() {
         println!("     没有找到相关文档");
     }
 
-    for (_score, doc_address) in top_docs {
-        let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+    for hit in &page.hits {
+        println!(" [文档标题] {} (Score: {:.2})", hit.title, hit.score);
+        println!("    路径: {}", hit.path);
+        if !hit.tags.is_empty() {
+            println!("    标签: {}", hit.tags.join(" "));
+        }
+        // 片段里可能带着原文的换行，压成一行再打印
+        let snippet = hit.snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !snippet.is_empty() {
+            println!("    摘要: {}", snippet);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::indexer::init_ram_index;
+    use tantivy::doc;
+
+    // 测试用的内存索引：每项是 (路径, 正文, 标签)
+    pub(crate) fn index_with(docs: &[(&str, &str, &str)]) -> Index {
+        let (index, schema) = init_ram_index();
+        let field = |name| schema.get_field(name).unwrap();
+        let mut writer: tantivy::IndexWriter = index.writer(crate::config::MIN_WRITER_HEAP_SIZE).unwrap();
+        for (path, body, tags) in docs {
+            writer
+                .add_document(doc!(
+                    field("title") => *path,
+                    field("body") => *body,
+                    field("path") => *path,
+                    field("tags") => *tags,
+                    field("timestamp") => 0u64,
+                ))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        index
+    }
+
+    #[test]
+    fn out_of_range_paging_is_clamped() {
+        let index = index_with(&[("./docs/a.txt", "rust 搜索", ""), ("./docs/b.txt", "rust 索引", "")]);
 
-        let title = retrieved_doc.get_first(title_field).and_then(|v| v.as_str()).unwrap_or("无标题");
-        let path = retrieved_doc.get_first(path_field).and_then(|v| v.as_str()).unwrap_or("无路径");
-        
-        // 可选：在这里调用 extract::format_content_preview 来显示摘要
-        // 但为了性能，这里只显示标题和路径
-        println!("   [{}] (Score: {:.2}) \n       路径: {}", title, _score, path);
+        let huge = SearchOptions { offset: usize::MAX, limit: usize::MAX, file_type: None };
+        let page = search_page(&index, "rust", &huge).unwrap();
+        assert_eq!((page.offset, page.limit, page.total), (MAX_PAGE_OFFSET, MAX_PAGE_LIMIT, 2));
+        assert!(page.hits.is_empty());
+
+        let zero = SearchOptions { offset: 0, limit: 0, file_type: None };
+        let page = search_page(&index, "rust", &zero).unwrap();
+        assert_eq!((page.limit, page.hits.len()), (1, 1));
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn malformed_query_is_a_typed_error() {
+        let index = index_with(&[("./docs/a.txt", "rust", "")]);
+        let error = search_page(&index, "nosuchfield:rust", &SearchOptions::default()).unwrap_err();
+        assert!(error.downcast_ref::<QueryParseError>().is_some(), "{:#}", error);
+    }
+
+    // 标签词表用的索引：机器学习 3 篇 (其中一篇重复打了两次)，机器人 2 篇，其余各 1 篇
+    pub(crate) fn tagged_index() -> Index {
+        index_with(&[
+            ("./docs/a.txt", "", "机器学习 神经网络 机器学习"),
+            ("./docs/b.txt", "", "机器学习 机器人"),
+            ("./docs/c.md", "", "机器学习 机器人 rust"),
+            ("./docs/d.md", "", ""),
+        ])
+    }
+
+    #[test]
+    fn list_tags_counts_documents_per_tag() {
+        let tags = list_tags(&tagged_index(), "", 0).unwrap();
+        let expected = [("机器学习", 3), ("机器人", 2), ("rust", 1), ("神经网络", 1)];
+        assert_eq!(tags, expected.map(|(tag, count)| (tag.to_string(), count)));
+    }
+
+    #[test]
+    fn list_tags_filters_by_cjk_prefix_and_limits() {
+        let index = tagged_index();
+        let names = |prefix: &str, limit: usize| -> Vec<String> { list_tags(&index, prefix, limit).unwrap().into_iter().map(|(tag, _)| tag).collect() };
+
+        assert_eq!(names("机器", 0), ["机器学习", "机器人"]);
+        assert_eq!(names("机器人", 0), ["机器人"]);
+        assert_eq!(names("神", 0), ["神经网络"]);
+        assert!(names("学习", 0).is_empty());
+        assert_eq!(names("", 2), ["机器学习", "机器人"]);
+        assert_eq!(names("机器", 1), ["机器学习"]);
+    }
+}
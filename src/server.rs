@@ -0,0 +1,246 @@
+// server.rs
+// HTTP 接口 (编译时需要 --features server)：浏览器插件、局域网里的手机等通过 HTTP 查询和更新索引。
+// 所有路由共享同一个 AppState；tantivy 和模型的调用都是阻塞的，统一放到 spawn_blocking 里执行
+use std::convert::Infallible;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::Router;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, FromRequestParts, Json, Request, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use futures_util::Stream;
+
+use crate::app::AppState;
+use crate::config::Config;
+use crate::indexer;
+use crate::api::{
+    ApiErrorKind, BatchIndexRequest, BatchIndexResponse, DeleteRequest, ErrorResponse, IndexRequest, IndexResult, SearchRequest,
+    SearchResponse, StatsResponse, token_matches,
+};
+use crate::search;
+
+#[derive(Clone)]
+struct ServerState {
+    app: Arc<AppState>,
+    token: Option<Arc<str>>, // 设置后，修改索引的接口需要带 Authorization: Bearer <token>
+}
+
+// 接口错误：HTTP 状态码 + 给调用方看的说明，响应体是 ErrorResponse
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    fn from_kind(kind: ApiErrorKind, message: impl Into<String>) -> Self {
+        let status = match kind {
+            ApiErrorKind::InvalidRequest => StatusCode::BAD_REQUEST,
+            ApiErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiErrorKind::Forbidden => StatusCode::FORBIDDEN,
+            ApiErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ApiErrorKind::Unprocessable => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiErrorKind::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            ApiErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, message)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::from_kind(ApiErrorKind::classify(&e), format!("{:#}", e))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorResponse { error: self.message })).into_response()
+    }
+}
+
+// 请求体：解析失败 (不是 JSON、缺字段、类型不对) 也返回 ErrorResponse，而不是 axum 默认的纯文本
+struct ApiJson<T>(T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, ApiError> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|rejection| ApiError::from_kind(ApiErrorKind::InvalidRequest, rejection.body_text()))?;
+        Ok(ApiJson(value))
+    }
+}
+
+// 修改索引的接口要求带令牌。它在参数列表里排在请求体前面，
+// 所以令牌不对时直接返回 401，不会先去解析请求体
+struct Authorized;
+
+#[axum::async_trait]
+impl FromRequestParts<ServerState> for Authorized {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &ServerState) -> Result<Self, ApiError> {
+        check_token(state, &parts.headers)?;
+        Ok(Authorized)
+    }
+}
+
+pub fn router(app: Arc<AppState>, token: Option<String>) -> Router {
+    let state = ServerState { app, token: token.map(Arc::from) };
+    Router::new()
+        .route("/search", post(search_handler))
+        .route("/index", post(index_handler))
+        .route("/index/batch", post(index_batch_handler))
+        .route("/document", delete(delete_handler))
+        .route("/stats", get(stats_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/events", get(events_handler))
+        .with_state(state)
+}
+
+// 在已经绑定好的端口上启动 HTTP 服务并阻塞，直到 app 的退出标志被置位 (Ctrl-C)。
+// 端口由调用方先绑定，这样可以先监听、再在后台开始扫描
+pub fn serve(app: Arc<AppState>, listener: TcpListener, token: Option<String>) -> Result<()> {
+    let addr = listener.local_addr()?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    runtime.block_on(async move {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        println!(" [服务] HTTP 接口已启动: http://{}", addr);
+
+        let shutdown_app = app.clone();
+        axum::serve(listener, router(app, token))
+            .with_graceful_shutdown(async move {
+                while !shutdown_app.is_shutting_down() {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+            })
+            .await?;
+        Ok(())
+    })
+}
+
+async fn search_handler(State(state): State<ServerState>, ApiJson(request): ApiJson<SearchRequest>) -> Result<Json<SearchResponse>, ApiError> {
+    if request.query.trim().is_empty() {
+        return Err(ApiError::from_kind(ApiErrorKind::InvalidRequest, "query 不能为空"));
+    }
+    let app = state.app.clone();
+    let page = run_blocking(move || search::search_page(&app.index, &request.query, &request.options)).await?;
+    Ok(Json(page))
+}
+
+async fn index_handler(State(state): State<ServerState>, _: Authorized, ApiJson(request): ApiJson<IndexRequest>) -> Result<Json<IndexResult>, ApiError> {
+    let config = state.app.config();
+    let path = resolve_index_path(&request.path, &config)?;
+
+    let app = state.app.clone();
+    let paths = vec![path.clone()];
+    let mut results = run_blocking(move || indexer::index_paths(&paths, &app.index, &app.schema, app.bert.as_deref(), &config, app.events())).await?;
+    match results.remove(0) {
+        Ok(title) => Ok(Json(IndexResult { path, title: Some(title), error: None })),
+        Err(e) => Err(ApiError::from_kind(ApiErrorKind::Unprocessable, format!("处理文件失败 {:?}: {:#}", path, e))),
+    }
+}
+
+async fn index_batch_handler(
+    State(state): State<ServerState>,
+    _: Authorized,
+    ApiJson(request): ApiJson<BatchIndexRequest>,
+) -> Result<Json<BatchIndexResponse>, ApiError> {
+    let app = state.app.clone();
+    let response = run_blocking(move || BatchIndexResponse::run(request, &app.index, &app.schema, app.bert.as_deref(), &app.config(), app.events())).await?;
+    Ok(Json(response))
+}
+
+async fn delete_handler(State(state): State<ServerState>, _: Authorized, ApiJson(request): ApiJson<DeleteRequest>) -> Result<StatusCode, ApiError> {
+    let app = state.app.clone();
+    let path = request.path.clone();
+    let deleted = run_blocking(move || indexer::delete_document(&app.index, &app.schema, &path, &app.config(), app.events())).await?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::from_kind(ApiErrorKind::NotFound, format!("索引中没有这个文件: {}", request.path)))
+    }
+}
+
+async fn stats_handler(State(state): State<ServerState>) -> Result<Json<StatsResponse>, ApiError> {
+    let app = state.app.clone();
+    let stats = run_blocking(move || StatsResponse::collect(&app.index, app.bert.is_some(), &app.config())).await?;
+    Ok(Json(stats))
+}
+
+async fn healthz_handler() -> &'static str {
+    "ok"
+}
+
+// 实时事件流 (Server-Sent Events)：event 字段是事件类型，data 是 EngineEvent 的 JSON。
+// 订阅队列是有界的，客户端读得慢只会丢旧事件 (并收到一条 lagged)，不会拖慢索引；
+// 等事件用的是异步通知，连接再多也不占用 spawn_blocking 的线程
+async fn events_handler(State(state): State<ServerState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let subscription = state.app.events().subscribe();
+    let stream = futures_util::stream::unfold((subscription, state.app.clone()), |(subscription, app)| async move {
+        loop {
+            // 服务退出时结束事件流，否则 graceful shutdown 会一直等这个连接
+            if app.is_shutting_down() {
+                return None;
+            }
+            // 定时醒来检查退出标志；超时没有新事件就继续等，连接保活由 KeepAlive 负责
+            if let Ok(event) = tokio::time::timeout(Duration::from_millis(500), subscription.recv()).await {
+                let data = serde_json::to_string(&event).unwrap_or_default();
+                let sse_event = Event::default().event(event.kind()).data(data);
+                return Some((Ok(sse_event), (subscription, app)));
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn resolve_index_path(path: &Path, config: &Config) -> Result<PathBuf, ApiError> {
+    indexer::resolve_external_path(path, config).map_err(|rejection| ApiError::from_kind(rejection.into(), format!("{:?}: {}", path, rejection)))
+}
+
+// 配置了 token 时，修改索引的接口必须带 Authorization: Bearer <token>
+fn check_token(state: &ServerState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(token) = &state.token else {
+        return Ok(());
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if token_matches(token, provided) {
+        Ok(())
+    } else {
+        Err(ApiError::from_kind(ApiErrorKind::Unauthorized, "缺少或错误的访问令牌 (Authorization: Bearer <token>)"))
+    }
+}
+
+async fn run_blocking<T, F>(f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ApiError::from_kind(ApiErrorKind::Internal, format!("后台任务异常退出: {}", e)))?
+        .map_err(ApiError::from)
+}
@@ -0,0 +1,523 @@
+// tui.rs
+// 终端界面：顶部输入框，左侧结果列表，右侧预览，底部状态栏
+// 状态与按键处理都在 App 里，和终端后端无关；搜索放在后台线程，输入不会卡顿
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tantivy::Index;
+use unicode_width::UnicodeWidthStr;
+
+use crate::search::{search_page, SearchHit, SearchOptions, SearchPage};
+
+pub const PAGE_SIZE: usize = 10;
+// 停止输入多久后才真正发起搜索
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+// Tab 依次切换的文件类型过滤
+const FILE_TYPES: [Option<&str>; 4] = [None, Some("pdf"), Some("md"), Some("txt")];
+
+// 发给后台搜索线程的任务，id 用来丢弃过期的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchJob {
+    pub id: u64,
+    pub query: String,
+    pub options: SearchOptions,
+}
+
+// 后台线程返回的 (任务 id, 结果)
+type SearchResult = (u64, Result<SearchPage, String>);
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Open(String), // 用系统默认程序打开文件
+}
+
+pub struct App {
+    pub query: String,
+    pub file_type: Option<String>,
+    pub page: usize,
+    pub results: Vec<SearchHit>,
+    pub total: usize,
+    pub selected: usize,
+    pub status: String,
+    pub should_quit: bool,
+    dirty_since: Option<Instant>, // 查询条件变化的时间，None 表示不需要重新搜索
+    search_now: bool,             // 翻页、切换过滤时不等防抖
+    next_job_id: u64,
+    latest_job_id: Option<u64>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            file_type: None,
+            page: 0,
+            results: Vec::new(),
+            total: 0,
+            selected: 0,
+            status: String::new(),
+            should_quit: false,
+            dirty_since: None,
+            search_now: false,
+            next_job_id: 0,
+            latest_job_id: None,
+        }
+    }
+
+    pub fn total_pages(&self) -> usize {
+        self.total.div_ceil(PAGE_SIZE).max(1)
+    }
+
+    pub fn selected_hit(&self) -> Option<&SearchHit> {
+        self.results.get(self.selected)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, now: Instant) -> Action {
+        // Windows 上松开按键也会产生事件
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => self.should_quit = true,
+            KeyCode::Char('c') if ctrl => self.should_quit = true,
+            KeyCode::Char('n') if ctrl => self.change_page(self.page + 1, now),
+            KeyCode::Char('p') if ctrl => self.change_page(self.page.saturating_sub(1), now),
+            KeyCode::PageDown => self.change_page(self.page + 1, now),
+            KeyCode::PageUp => self.change_page(self.page.saturating_sub(1), now),
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.query_changed(now);
+            }
+            KeyCode::Backspace if !self.query.is_empty() => {
+                self.query.pop();
+                self.query_changed(now);
+            }
+            KeyCode::Down if self.selected + 1 < self.results.len() => self.selected += 1,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Tab => {
+                let current = FILE_TYPES.iter().position(|t| t.map(str::to_string) == self.file_type).unwrap_or(0);
+                self.file_type = FILE_TYPES[(current + 1) % FILE_TYPES.len()].map(str::to_string);
+                self.page = 0;
+                self.request_search_now(now);
+            }
+            KeyCode::Enter => {
+                if let Some(hit) = self.selected_hit() {
+                    return Action::Open(hit.path.clone());
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    // 到了该搜索的时候返回一个任务；调用方负责把它发给后台线程
+    pub fn poll_search(&mut self, now: Instant) -> Option<SearchJob> {
+        let since = self.dirty_since?;
+        if !self.search_now && now.duration_since(since) < DEBOUNCE {
+            return None;
+        }
+        self.dirty_since = None;
+        self.search_now = false;
+
+        if self.query.trim().is_empty() {
+            self.latest_job_id = None;
+            self.results.clear();
+            self.total = 0;
+            self.selected = 0;
+            self.status.clear();
+            return None;
+        }
+
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        self.latest_job_id = Some(id);
+        self.status = "搜索中...".to_string();
+        Some(SearchJob {
+            id,
+            query: self.query.clone(),
+            options: SearchOptions {
+                offset: self.page * PAGE_SIZE,
+                limit: PAGE_SIZE,
+                file_type: self.file_type.clone(),
+            },
+        })
+    }
+
+    // 后台线程返回结果；不是最新任务的结果直接丢掉
+    pub fn apply_result(&mut self, id: u64, result: Result<SearchPage, String>) {
+        if self.latest_job_id != Some(id) {
+            return;
+        }
+        match result {
+            Ok(page) => {
+                self.results = page.hits;
+                self.total = page.total;
+                self.selected = 0;
+                self.status = if self.total == 0 { "没有找到相关文档".to_string() } else { String::new() };
+            }
+            Err(e) => {
+                self.results.clear();
+                self.total = 0;
+                self.selected = 0;
+                self.status = e;
+            }
+        }
+    }
+
+    fn query_changed(&mut self, now: Instant) {
+        self.page = 0;
+        self.dirty_since = Some(now);
+    }
+
+    fn request_search_now(&mut self, now: Instant) {
+        self.dirty_since = Some(now);
+        self.search_now = true;
+    }
+
+    fn change_page(&mut self, page: usize, now: Instant) {
+        let page = page.min(self.total_pages() - 1);
+        if page != self.page {
+            self.page = page;
+            self.request_search_now(now);
+        }
+    }
+}
+
+// 启动 TUI，直到用户按 Esc / Ctrl-C 退出
+pub fn run(index: Index) -> Result<()> {
+    let (job_tx, result_rx) = spawn_search_worker(index);
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &job_tx, &result_rx);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    job_tx: &Sender<SearchJob>,
+    result_rx: &Receiver<SearchResult>,
+) -> Result<()> {
+    let mut app = App::new();
+    while !app.should_quit {
+        terminal.draw(|frame| draw(frame, &app))?;
+
+        if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+            && let Action::Open(path) = app.handle_key(key, Instant::now())
+        {
+            app.status = open_file(&path);
+        }
+        if let Some(job) = app.poll_search(Instant::now()) {
+            job_tx.send(job)?;
+        }
+        while let Ok((id, result)) = result_rx.try_recv() {
+            app.apply_result(id, result);
+        }
+    }
+    Ok(())
+}
+
+fn spawn_search_worker(index: Index) -> (Sender<SearchJob>, Receiver<SearchResult>) {
+    let (job_tx, job_rx) = mpsc::channel::<SearchJob>();
+    let (result_tx, result_rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        while let Ok(mut job) = job_rx.recv() {
+            // 积压的任务只做最新的那个
+            while let Ok(newer) = job_rx.try_recv() {
+                job = newer;
+            }
+            let result = search_page(&index, &job.query, &job.options).map_err(|e| e.to_string());
+            if result_tx.send((job.id, result)).is_err() {
+                break;
+            }
+        }
+    });
+
+    (job_tx, result_rx)
+}
+
+// 用系统默认程序打开文件；Windows 的 start 是 cmd 内建命令，第一个引号参数是窗口标题，要留空
+fn open_file(path: &str) -> String {
+    let mut command = if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let spawned = command
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        Ok(_) => format!("已打开: {}", path),
+        Err(e) => format!("打开失败: {}", e),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [input_area, body_area, status_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [list_area, preview_area] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(body_area);
+
+    // 输入框
+    let input = Paragraph::new(app.query.as_str()).block(Block::bordered().title(" 搜索 "));
+    frame.render_widget(input, input_area);
+    frame.set_cursor_position(Position::new(
+        input_area.x + 1 + app.query.width() as u16,
+        input_area.y + 1,
+    ));
+
+    // 结果列表
+    let items: Vec<ListItem> = app.results.iter().map(|hit| {
+        ListItem::new(Text::from(vec![
+            Line::from(vec![
+                Span::styled(hit.title.clone(), Style::new().add_modifier(Modifier::BOLD)),
+                Span::styled(format!("  ({:.2})", hit.score), Style::new().fg(Color::DarkGray)),
+            ]),
+            Line::from(format!("  {}", hit.path)),
+            Line::from(Span::styled(format!("  标签: {}", hit.tags.join(" ")), Style::new().fg(Color::Cyan))),
+        ]))
+    }).collect();
+    let list = List::new(items)
+        .block(Block::bordered().title(" 结果 "))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default().with_selected(app.selected_hit().map(|_| app.selected));
+    frame.render_stateful_widget(list, list_area, &mut list_state);
+
+    // 预览：命中词高亮
+    let preview = match app.selected_hit() {
+        Some(hit) => {
+            let mut lines = vec![
+                Line::from(Span::styled(hit.title.clone(), Style::new().add_modifier(Modifier::BOLD))),
+                Line::from(format!("路径: {}", hit.path)),
+                Line::from(format!("标签: {}", hit.tags.join(" "))),
+                Line::from(""),
+            ];
+            lines.extend(highlighted_lines(&hit.snippet, &hit.highlights));
+            Text::from(lines)
+        }
+        None => Text::from(""),
+    };
+    let preview = Paragraph::new(preview)
+        .block(Block::bordered().title(" 预览 "))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(preview, preview_area);
+
+    // 状态栏
+    let file_type = app.file_type.as_deref().unwrap_or("全部");
+    let status = format!(
+        " 第 {}/{} 页，共 {} 条 | 类型: {} | Enter 打开  Tab 切换类型  PgUp/PgDn 翻页  Esc 退出  {}",
+        app.page + 1,
+        app.total_pages(),
+        app.total,
+        file_type,
+        app.status
+    );
+    frame.render_widget(Paragraph::new(status).style(Style::new().fg(Color::Black).bg(Color::Gray)), status_area);
+}
+
+// 把 snippet 按命中范围切成普通/高亮片段，并保留原文换行
+fn highlighted_lines(snippet: &str, highlights: &[std::ops::Range<usize>]) -> Vec<Line<'static>> {
+    let highlight_style = Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let mut lines = Vec::new();
+    let mut spans = Vec::new();
+
+    fn push_text(text: &str, style: Style, spans: &mut Vec<Span<'static>>, lines: &mut Vec<Line<'static>>) {
+        let mut parts = text.split('\n');
+        if let Some(first) = parts.next() {
+            spans.push(Span::styled(first.to_string(), style));
+        }
+        for part in parts {
+            lines.push(Line::from(std::mem::take(spans)));
+            spans.push(Span::styled(part.to_string(), style));
+        }
+    }
+
+    let mut cursor = 0;
+    for range in highlights {
+        if range.start < cursor || range.end > snippet.len() {
+            continue;
+        }
+        push_text(&snippet[cursor..range.start], Style::new(), &mut spans, &mut lines);
+        push_text(&snippet[range.clone()], highlight_style, &mut spans, &mut lines);
+        cursor = range.end;
+    }
+    push_text(&snippet[cursor..], Style::new(), &mut spans, &mut lines);
+    lines.push(Line::from(spans));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_text(app: &mut App, text: &str, now: Instant) {
+        for c in text.chars() {
+            assert_eq!(app.handle_key(key(KeyCode::Char(c)), now), Action::None);
+        }
+    }
+
+    fn hit(path: &str) -> SearchHit {
+        SearchHit { title: path.to_string(), path: path.to_string(), tags: Vec::new(), score: 1.0, snippet: String::new(), highlights: Vec::new() }
+    }
+
+    fn page(total: usize, paths: &[&str]) -> Result<SearchPage, String> {
+        Ok(SearchPage { hits: paths.iter().map(|path| hit(path)).collect(), total, offset: 0, limit: PAGE_SIZE })
+    }
+
+    // 输入查询并等防抖结束，把结果 (共 total 条) 交给 App
+    fn searched(total: usize, paths: &[&str]) -> (App, Instant) {
+        let mut app = App::new();
+        let now = Instant::now();
+        type_text(&mut app, "rust", now);
+        let job = app.poll_search(now + DEBOUNCE).unwrap();
+        app.apply_result(job.id, page(total, paths));
+        (app, now + DEBOUNCE)
+    }
+
+    #[test]
+    fn typing_waits_for_the_debounce_window() {
+        let mut app = App::new();
+        let start = Instant::now();
+        type_text(&mut app, "ru", start);
+        assert_eq!(app.poll_search(start + DEBOUNCE / 2), None);
+
+        // 继续输入会重新计时
+        type_text(&mut app, "st", start + DEBOUNCE / 2);
+        assert_eq!(app.poll_search(start + DEBOUNCE), None);
+        let job = app.poll_search(start + DEBOUNCE / 2 + DEBOUNCE).unwrap();
+        assert_eq!(job.query, "rust");
+        assert_eq!(job.options, SearchOptions { offset: 0, limit: PAGE_SIZE, file_type: None });
+        assert_eq!(app.status, "搜索中...");
+        // 一次变化只发一个任务
+        assert_eq!(app.poll_search(start + DEBOUNCE * 10), None);
+
+        // 删光之后清空结果，不发任务
+        for _ in 0..4 {
+            app.handle_key(key(KeyCode::Backspace), start);
+        }
+        assert_eq!(app.poll_search(start + DEBOUNCE), None);
+        assert!(app.query.is_empty() && app.results.is_empty());
+    }
+
+    #[test]
+    fn tab_cycles_file_type_filters_and_searches_immediately() {
+        let (mut app, now) = searched(25, &["a.txt"]);
+        app.handle_key(key(KeyCode::PageDown), now);
+        app.poll_search(now);
+
+        let mut seen = Vec::new();
+        for _ in 0..FILE_TYPES.len() {
+            app.handle_key(key(KeyCode::Tab), now);
+            let job = app.poll_search(now).expect("切换过滤后不等防抖");
+            assert_eq!(job.options.offset, 0, "切换过滤回到第一页");
+            seen.push(job.options.file_type);
+        }
+        let expected: Vec<Option<String>> = ["pdf", "md", "txt"].iter().map(|t| Some(t.to_string())).chain([None]).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn page_keys_are_clamped_to_existing_pages() {
+        let (mut app, now) = searched(25, &["a.txt"]);
+        assert_eq!(app.total_pages(), 3);
+
+        app.handle_key(key(KeyCode::PageUp), now);
+        assert_eq!(app.poll_search(now), None, "第一页再往前不发任务");
+
+        app.handle_key(key(KeyCode::PageDown), now);
+        assert_eq!(app.poll_search(now).unwrap().options.offset, PAGE_SIZE);
+        app.handle_key(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL), now);
+        assert_eq!(app.poll_search(now).unwrap().options.offset, 2 * PAGE_SIZE);
+        app.handle_key(key(KeyCode::PageDown), now);
+        assert_eq!(app.poll_search(now), None, "最后一页再往后不发任务");
+        assert_eq!(app.page, 2);
+
+        app.handle_key(key(KeyCode::PageUp), now);
+        assert_eq!(app.poll_search(now).unwrap().options.offset, PAGE_SIZE);
+    }
+
+    #[test]
+    fn stale_results_are_dropped() {
+        let mut app = App::new();
+        let now = Instant::now();
+        type_text(&mut app, "r", now);
+        let old = app.poll_search(now + DEBOUNCE).unwrap();
+        type_text(&mut app, "ust", now + DEBOUNCE);
+        let new = app.poll_search(now + DEBOUNCE * 2).unwrap();
+        assert_ne!(old.id, new.id);
+
+        app.apply_result(new.id, page(1, &["new.txt"]));
+        // 旧任务的结果晚到，不能覆盖新结果
+        app.apply_result(old.id, page(2, &["old.txt", "old.md"]));
+        assert_eq!(app.total, 1);
+        assert_eq!(app.selected_hit().unwrap().path, "new.txt");
+
+        app.apply_result(new.id, Err("查询语法错误".to_string()));
+        assert!(app.results.is_empty());
+        assert_eq!(app.status, "查询语法错误");
+    }
+
+    #[test]
+    fn enter_opens_the_selected_hit() {
+        let (mut app, now) = searched(3, &["a.txt", "b.md", "c.pdf"]);
+        assert_eq!(app.handle_key(key(KeyCode::Enter), now), Action::Open("a.txt".to_string()));
+
+        app.handle_key(key(KeyCode::Down), now);
+        app.handle_key(key(KeyCode::Down), now);
+        app.handle_key(key(KeyCode::Down), now); // 已经是最后一条
+        assert_eq!(app.handle_key(key(KeyCode::Enter), now), Action::Open("c.pdf".to_string()));
+        app.handle_key(key(KeyCode::Up), now);
+        assert_eq!(app.handle_key(key(KeyCode::Enter), now), Action::Open("b.md".to_string()));
+
+        assert_eq!(App::new().handle_key(key(KeyCode::Enter), now), Action::None);
+    }
+
+    #[test]
+    fn escape_and_ctrl_c_quit_and_key_releases_are_ignored() {
+        let now = Instant::now();
+        let mut app = App::new();
+        let mut release = key(KeyCode::Char('x'));
+        release.kind = KeyEventKind::Release;
+        app.handle_key(release, now);
+        assert!(app.query.is_empty());
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL), now);
+        assert!(app.should_quit && app.query.is_empty());
+
+        let mut app = App::new();
+        app.handle_key(key(KeyCode::Esc), now);
+        assert!(app.should_quit);
+    }
+}
@@ -0,0 +1,72 @@
+// 命令行的集成测试：直接运行编译好的 ai_search
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+// --no-ai 时交互模式照常扫描和搜索，但完全不碰模型：不下载、不创建缓存目录
+#[test]
+fn no_ai_search_never_touches_model_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    fs::create_dir(&docs).unwrap();
+    fs::write(docs.join("rust.txt"), "Rust 所有权与借用").unwrap();
+    let models = dir.path().join("models");
+    let config = dir.path().join("ai_search.toml");
+    fs::write(
+        &config,
+        format!("[index]\nstorage_path = {:?}\n\n[ai]\nmodel_path = {:?}\n\n[watch]\nroots = [{:?}]\n", dir.path().join("storage"), models, docs),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ai_search"))
+        .args(["--config", config.to_str().unwrap(), "--no-ai"])
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut output = Vec::new();
+
+    // 等初始扫描完成再搜索，输入 quit 后读到进程退出
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        let line = line.unwrap();
+        if line.contains("初始索引完成") {
+            writeln!(stdin, "所有权\nquit").unwrap();
+        }
+        output.push(line);
+    }
+    assert!(child.wait().unwrap().success());
+
+    let output = output.join("\n");
+    assert!(output.contains("已禁用"), "{}", output);
+    assert!(output.contains(&format!("路径: {}", docs.join("rust.txt").display())), "{}", output);
+    assert!(output.contains("摘要: Rust 所有权与借用"), "{}", output);
+    assert!(!models.exists(), "不应该创建模型缓存目录");
+    assert!(!dir.path().join(".fastembed_cache").exists(), "不应该创建默认的模型缓存目录");
+}
+
+// --model-path 指向的本地目录缺文件时直接报错退出，不会退回去下载模型
+#[test]
+fn model_path_with_missing_files_fails_without_downloading() {
+    let dir = tempfile::tempdir().unwrap();
+    let models = dir.path().join("models");
+    fs::create_dir(&models).unwrap();
+    fs::write(models.join("tokenizer.json"), "{}").unwrap();
+    let config = dir.path().join("ai_search.toml");
+    fs::write(&config, format!("[index]\nstorage_path = {:?}\n\n[watch]\nroots = [{:?}]\n", dir.path().join("storage"), dir.path().join("docs"))).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_ai_search"))
+        .args(["--config", config.to_str().unwrap(), "--model-path", models.to_str().unwrap()])
+        .current_dir(dir.path())
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("缺少文件") && stderr.contains("onnx/model.onnx"), "{}", stderr);
+    assert_eq!(fs::read_dir(&models).unwrap().count(), 1, "不应该往本地模型目录里下载文件");
+    assert!(!dir.path().join(".fastembed_cache").exists(), "不应该退回到默认缓存目录下载");
+}
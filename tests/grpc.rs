@@ -0,0 +1,107 @@
+// gRPC 接口的集成测试：不监听端口，直接调用 GrpcService 的方法，索引放在内存里
+#![cfg(feature = "grpc")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ai_search_demo::app::AppState;
+use ai_search_demo::config::{Config, ConfigHandle, MIN_WRITER_HEAP_SIZE, RootConfig};
+use ai_search_demo::grpc::GrpcService;
+use ai_search_demo::grpc::proto::ai_search_server::AiSearch;
+use ai_search_demo::grpc::proto::{BatchIndexRequest, IndexRequest, SearchRequest, StatsRequest};
+use tempfile::TempDir;
+use tonic::{Code, Request};
+
+const TOKEN: &str = "secret";
+
+struct TestService {
+    _dir: TempDir,
+    root: PathBuf,
+    service: GrpcService,
+}
+
+// 监控目录下放两个文件，还没有索引
+fn test_service() -> TestService {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("docs");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("rust.txt"), "Rust 所有权与借用").unwrap();
+    fs::write(root.join("notes.md"), "神经网络 学习笔记").unwrap();
+
+    let mut config = Config::default();
+    config.ai.enabled = false;
+    config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+    config.watch.roots = vec![RootConfig::new(&root)];
+
+    let (index, schema) = ai_search_demo::indexer::init_ram_index();
+    let app = Arc::new(AppState::new(index, schema, None, ConfigHandle::new(config)));
+    TestService { _dir: dir, root, service: GrpcService::new(app, Some(TOKEN.to_string())) }
+}
+
+impl TestService {
+    // 带上令牌批量索引监控目录下的两个文件
+    async fn index_all(&self) {
+        let paths = ["rust.txt", "notes.md"].iter().map(|name| self.root.join(name).to_string_lossy().into_owned()).collect();
+        let mut request = Request::new(BatchIndexRequest { paths });
+        request.metadata_mut().insert("authorization", format!("Bearer {}", TOKEN).parse().unwrap());
+        let response = self.service.batch_index(request).await.unwrap().into_inner();
+        assert_eq!((response.indexed, response.failed), (2, 0));
+    }
+
+    async fn search(&self, request: SearchRequest) -> Result<ai_search_demo::grpc::proto::SearchResponse, tonic::Status> {
+        self.service.search(Request::new(request)).await.map(|response| response.into_inner())
+    }
+}
+
+#[tokio::test]
+async fn stats_reports_documents_and_roots() {
+    let service = test_service();
+    let stats = service.service.stats(Request::new(StatsRequest {})).await.unwrap().into_inner();
+    assert_eq!((stats.num_docs, stats.ai_enabled), (0, false));
+    assert_eq!(stats.watch_roots, [service.root.to_string_lossy()]);
+
+    service.index_all().await;
+    let stats = service.service.stats(Request::new(StatsRequest {})).await.unwrap().into_inner();
+    assert_eq!(stats.num_docs, 2);
+}
+
+#[tokio::test]
+async fn search_returns_paged_hits() {
+    let service = test_service();
+    service.index_all().await;
+
+    let page = service.search(SearchRequest { query: "所有权".to_string(), ..Default::default() }).await.unwrap();
+    assert_eq!((page.total, page.offset, page.limit), (1, 0, 5));
+    assert_eq!(page.hits[0].path, service.root.join("rust.txt").to_string_lossy());
+    assert!(!page.hits[0].highlights.is_empty());
+
+    // 空的 file_type 等于不过滤，其他扩展名按后缀过滤
+    let all = SearchRequest { query: "所有权 OR 神经网络".to_string(), file_type: Some(String::new()), ..Default::default() };
+    assert_eq!(service.search(all.clone()).await.unwrap().total, 2);
+    let md = SearchRequest { file_type: Some("md".to_string()), ..all.clone() };
+    let page = service.search(md).await.unwrap();
+    assert_eq!(page.total, 1);
+    assert!(page.hits[0].path.ends_with("notes.md"));
+
+    let second = SearchRequest { offset: Some(1), limit: Some(1), ..all };
+    let page = service.search(second).await.unwrap();
+    assert_eq!((page.total, page.offset, page.limit, page.hits.len()), (2, 1, 1, 1));
+}
+
+#[tokio::test]
+async fn search_errors_are_invalid_arguments() {
+    let service = test_service();
+    for query in ["  ", "nosuchfield:rust"] {
+        let error = service.search(SearchRequest { query: query.to_string(), ..Default::default() }).await.unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument, "{:?}", query);
+    }
+}
+
+#[tokio::test]
+async fn write_methods_require_the_bearer_token() {
+    let service = test_service();
+    let path = service.root.join("rust.txt").to_string_lossy().into_owned();
+    let error = service.service.index_file(Request::new(IndexRequest { path })).await.unwrap_err();
+    assert_eq!(error.code(), Code::Unauthenticated);
+}
@@ -0,0 +1,52 @@
+// 主流程的端到端测试：和交互模式一样建持久化索引、扫描监控目录，再做带过滤的搜索
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::time::UNIX_EPOCH;
+
+use ai_search_demo::config::{Config, ConfigHandle, MIN_WRITER_HEAP_SIZE, RootConfig};
+use ai_search_demo::events::EventBus;
+use ai_search_demo::indexer::{init_persistent_index, scan_existing_files};
+use ai_search_demo::search::{SearchOptions, get_document, search_page};
+
+#[test]
+fn scan_then_filtered_search_returns_stored_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    fs::create_dir_all(docs.join("notes")).unwrap();
+    fs::write(docs.join("rust.txt"), "Rust 所有权与借用检查").unwrap();
+    fs::write(docs.join("notes").join("rust.md"), "# Rust 笔记\n所有权规则整理").unwrap();
+    fs::write(docs.join("ignored.log"), "所有权").unwrap();
+
+    let mut config = Config::default();
+    config.ai.enabled = false;
+    config.index.storage_path = dir.path().join("storage");
+    config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+    config.watch.roots = vec![RootConfig::new(&docs)];
+    let roots = config.watch.root_paths();
+    let storage = config.index.storage_path.clone();
+
+    let (index, schema) = init_persistent_index(&storage).unwrap();
+    scan_existing_files(&roots, &index, &schema, None, &ConfigHandle::new(config), &EventBus::default(), &AtomicBool::new(false)).unwrap();
+    drop(index);
+
+    // 重新打开落盘的索引，和之后的 tags / tui 命令看到的一样
+    let (index, _schema) = init_persistent_index(&storage).unwrap();
+    let all = search_page(&index, "所有权", &SearchOptions::default()).unwrap();
+    assert_eq!(all.total, 2, "log 文件不在扩展名白名单里");
+
+    let options = SearchOptions { file_type: Some("md".to_string()), ..SearchOptions::default() };
+    let page = search_page(&index, "所有权", &options).unwrap();
+    assert_eq!(page.total, 1);
+    let hit = &page.hits[0];
+    let path = docs.join("notes").join("rust.md");
+    assert_eq!(hit.path, path.to_string_lossy());
+    assert_eq!(hit.title, "rust");
+    assert!(hit.tags.is_empty(), "AI 关闭时不生成标签");
+    assert!(hit.snippet.contains("所有权"));
+
+    let stored = get_document(&index, &hit.path).unwrap().unwrap();
+    assert_eq!(stored.path, hit.path);
+    assert!(stored.body.contains("所有权规则整理"));
+    let modified = fs::metadata(&path).unwrap().modified().unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    assert_eq!(stored.timestamp, modified);
+}
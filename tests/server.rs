@@ -0,0 +1,258 @@
+// HTTP 接口的集成测试：不监听端口，直接用 oneshot 调用 router，索引放在内存里
+#![cfg(feature = "server")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ai_search_demo::app::AppState;
+use ai_search_demo::config::{Config, ConfigHandle, MIN_WRITER_HEAP_SIZE, RootConfig};
+use ai_search_demo::indexer::init_ram_index;
+use ai_search_demo::server::router;
+use axum::Router;
+use axum::body::{Body, BodyDataStream, to_bytes};
+use axum::http::{Request, StatusCode, header};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use tantivy::TantivyDocument;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+const TOKEN: &str = "secret";
+
+struct TestServer {
+    _dir: TempDir,
+    root: PathBuf,
+    app: Arc<AppState>,
+    router: Router,
+}
+
+// 监控目录下放两个文件，还没有索引
+fn test_server() -> TestServer {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("docs");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("rust.txt"), "Rust 所有权与借用").unwrap();
+    fs::write(root.join("notes.md"), "神经网络 学习笔记").unwrap();
+    fs::write(dir.path().join("secret.txt"), "监控目录外的文件").unwrap();
+
+    let mut config = Config::default();
+    config.ai.enabled = false;
+    config.index.heap_size = MIN_WRITER_HEAP_SIZE;
+    config.watch.roots = vec![RootConfig::new(&root)];
+
+    let (index, schema) = init_ram_index();
+    let app = Arc::new(AppState::new(index, schema, None, ConfigHandle::new(config)));
+    let router = router(app.clone(), Some(TOKEN.to_string()));
+    TestServer { _dir: dir, root, app, router }
+}
+
+impl TestServer {
+    async fn send(&self, method: &str, uri: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        self.send_raw(method, uri, token, body.map(|body| body.to_string())).await
+    }
+
+    // 请求体原样发送 (可以不是合法 JSON)，有请求体时带 JSON 的 Content-Type
+    async fn send_raw(&self, method: &str, uri: &str, token: Option<&str>, body: Option<String>) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body)
+            }
+            None => Body::empty(),
+        };
+        let response = self.router.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value = serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+        (status, value)
+    }
+
+    async fn index(&self, path: &Path) -> (StatusCode, Value) {
+        self.send("POST", "/index", Some(TOKEN), Some(json!({ "path": path }))).await
+    }
+}
+
+#[tokio::test]
+async fn healthz_and_stats() {
+    let server = test_server();
+    let (status, body) = server.send("GET", "/healthz", None, None).await;
+    assert_eq!((status, body), (StatusCode::OK, json!("ok")));
+
+    let (status, body) = server.send("GET", "/stats", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["num_docs"], 0);
+    assert_eq!(body["ai_enabled"], false);
+    assert_eq!(body["watch_roots"], json!([server.root]));
+}
+
+#[tokio::test]
+async fn index_then_search_then_delete() {
+    let server = test_server();
+    let path = server.root.join("rust.txt");
+
+    let (status, body) = server.index(&path).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["path"], json!(path));
+    assert_eq!(body["title"], "rust");
+
+    let (status, body) = server.send("POST", "/search", None, Some(json!({ "query": "所有权" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["hits"][0]["path"], json!(path));
+
+    let delete = json!({ "path": path });
+    let (status, _) = server.send("DELETE", "/document", Some(TOKEN), Some(delete.clone())).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = server.send("DELETE", "/document", Some(TOKEN), Some(delete)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn batch_index_reports_each_file() {
+    let server = test_server();
+    let paths = json!([server.root.join("rust.txt"), server.root.join("notes.md"), server.root.join("missing.txt")]);
+
+    let (status, body) = server.send("POST", "/index/batch", Some(TOKEN), Some(json!({ "paths": paths }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!((body["indexed"].clone(), body["failed"].clone()), (json!(2), json!(1)));
+
+    let (_, stats) = server.send("GET", "/stats", None, None).await;
+    assert_eq!(stats["num_docs"], 2);
+}
+
+#[tokio::test]
+async fn write_routes_require_the_bearer_token() {
+    let server = test_server();
+    let index = json!({ "path": server.root.join("rust.txt") });
+    let requests = [
+        ("POST", "/index", index.clone()),
+        ("POST", "/index/batch", json!({ "paths": [server.root.join("rust.txt")] })),
+        ("DELETE", "/document", index),
+    ];
+    for (method, uri, body) in requests {
+        for token in [None, Some("wrong")] {
+            let (status, body) = server.send(method, uri, token, Some(body.clone())).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {} {:?}", method, uri, token);
+            assert!(body["error"].is_string());
+        }
+    }
+
+    // 只读接口不需要令牌
+    let (status, _) = server.send("POST", "/search", None, Some(json!({ "query": "rust" }))).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn token_is_checked_before_the_body_is_parsed() {
+    let server = test_server();
+    for uri in ["/index", "/index/batch"] {
+        for body in [None, Some("{not json".to_string()), Some("{}".to_string())] {
+            let (status, response) = server.send_raw("POST", uri, Some("wrong"), body.clone()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {:?}", uri, body);
+            assert!(response["error"].is_string());
+        }
+    }
+}
+
+#[tokio::test]
+async fn malformed_bodies_get_json_errors() {
+    let server = test_server();
+    let bodies = [
+        ("POST", "/search", Some("{not json")),
+        ("POST", "/search", Some(r#"{"limit": 5}"#)),
+        ("POST", "/search", Some(r#"{"query": 5}"#)),
+        ("POST", "/index", Some("[]")),
+        ("DELETE", "/document", None),
+    ];
+    for (method, uri, body) in bodies {
+        let (status, response) = server.send_raw(method, uri, Some(TOKEN), body.map(String::from)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} {:?}", uri, body);
+        assert!(response["error"].as_str().is_some_and(|error| !error.is_empty()), "{} {:?}: {}", uri, body, response);
+    }
+}
+
+#[tokio::test]
+async fn errors_map_to_status_codes() {
+    let server = test_server();
+
+    let (status, _) = server.send("POST", "/search", None, Some(json!({ "query": "  " }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = server.send("POST", "/search", None, Some(json!({ "query": "nosuchfield:rust" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    // 目录外的文件 (包括用 .. 绕出去的) 是 403，目录里不存在或被过滤的文件是 400
+    let (status, _) = server.index(&server.root.join("..").join("secret.txt")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = server.index(&server.root.join("missing.txt")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    fs::write(server.root.join("image.png"), "png").unwrap();
+    let (status, _) = server.index(&server.root.join("image.png")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // 写入锁被占用时是 503，释放之后就能写了
+    let writer = server.app.index.writer::<TantivyDocument>(MIN_WRITER_HEAP_SIZE).unwrap();
+    let (status, body) = server.index(&server.root.join("rust.txt")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{}", body);
+    drop(writer);
+    let (status, _) = server.index(&server.root.join("rust.txt")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// 从 SSE 响应体里读出下一条事件 (event 字段, data 的 JSON)，事件流结束时返回 None
+async fn next_event(body: &mut BodyDataStream, buffer: &mut String) -> Option<(String, Value)> {
+    loop {
+        if let Some(end) = buffer.find("\n\n") {
+            let frame: String = buffer.drain(..end + 2).collect();
+            let field = |name: &str| frame.lines().find_map(|line| line.strip_prefix(name)).map(str::trim).map(str::to_string);
+            // 保活的注释帧没有 event 字段，跳过
+            if let (Some(event), Some(data)) = (field("event:"), field("data:")) {
+                return Some((event, serde_json::from_str(&data).unwrap()));
+            }
+            continue;
+        }
+        let chunk = tokio::time::timeout(Duration::from_secs(10), body.next()).await.expect("等待事件超时")?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+    }
+}
+
+#[tokio::test]
+async fn events_stream_covers_background_scan() {
+    let server = test_server();
+    let response = server.router.clone().oneshot(Request::get("/events").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+    let mut body = response.into_body().into_data_stream();
+    let mut buffer = String::new();
+
+    // 先连上事件流、再开始后台扫描，客户端能从 ai_status 开始收到全部事件；扫描期间接口照常可用
+    server.app.start_indexing();
+    let (status, _) = server.send("GET", "/healthz", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 事件总线属于这个 AppState，收到的就是这次扫描的全部事件
+    let mut seen = Vec::new();
+    while let Some((event, data)) = next_event(&mut body, &mut buffer).await {
+        assert_eq!(data["type"], event.as_str());
+        if event == "scan_started" {
+            assert_eq!(data["roots"], json!([server.root]));
+        }
+        seen.push(event.clone());
+        if event == "scan_finished" {
+            break;
+        }
+    }
+    assert_eq!(seen, ["ai_status", "scan_started", "file_indexed", "file_indexed", "scan_finished"]);
+
+    let (_, stats) = server.send("GET", "/stats", None, None).await;
+    assert_eq!(stats["num_docs"], 2);
+
+    // 退出时事件流随之结束，graceful shutdown 不会被这个连接卡住
+    server.app.shutdown().unwrap();
+    while next_event(&mut body, &mut buffer).await.is_some() {}
+}